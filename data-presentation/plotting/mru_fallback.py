@@ -1,17 +1,20 @@
+import json
+
 import plotly.graph_objects as go
 import plotly.io as pio
 
 dpi = 2
-algorithms = ['MRU', 'MRU-2', 'MRU-4', 'MRU-8', 'MRU-16', 'MRU-32', 'MRU-64']
-values = [
-    1533.0 / 2593.0,
-    1611.0 / 2604.0,
-    1816.0 / 2627.0,
-    2152.0 / 2651.0,
-    2441.0 / 2666.0,
-    2593.0 / 2668.0,
-    2656.0 / 2669.0
-]
+runs = ['MRU', 'MRU.2', 'MRU.4', 'MRU.8', 'MRU.16', 'MRU.32', 'MRU.64']
+algorithms = [run.replace('.', '-') for run in runs]
+
+
+def fallback_ratio(run):
+    with open("../data/out/simulation/batch/" + run + "-FIFO.json") as f:
+        summary = json.load(f)
+    return summary["fallback_count"] / summary["decision_count"]
+
+
+values = [fallback_ratio(run) for run in runs]
 
 fig = go.Figure([go.Bar(x=algorithms, y=values)])
 
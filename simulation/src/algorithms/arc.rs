@@ -0,0 +1,99 @@
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+
+use super::ghost_list::GhostList;
+use crate::implementation::{
+    CleanupAlgorithm, CleanupDataSource, DataSource, PipelineID, StateChange,
+};
+
+#[derive(Default)]
+struct ARCState {
+    /// Target number of stored pipelines which have not been accessed yet (`p` in the paper)
+    target: f64,
+    /// Ghosts of pipelines evicted before they have been accessed (`B1` in the paper)
+    recent_ghosts: GhostList,
+    /// Ghosts of pipelines evicted after they have been accessed at least once (`B2` in the paper)
+    frequent_ghosts: GhostList,
+    /// Pipelines removed from storage since the last eviction alongside the time of their removal
+    removals: Vec<(i64, PipelineID)>,
+    /// Pipelines missed since the last eviction, once per missed access
+    misses: Vec<PipelineID>,
+}
+
+/// Adaptive Replacement Cache (Megiddo and Modha, 2003). Storing a pipeline counts as its first reference,
+/// so stored pipelines which have not been accessed yet form the recency list `T1` while accessed ones form
+/// the frequency list `T2`. Since missed pipelines are not stored again, hits in the ghost lists only adapt
+/// the target size of `T1`. Sizes are counted in pipelines, not bytes.
+#[derive(Default)]
+pub struct ARCAlgorithm {
+    state: Mutex<ARCState>,
+}
+
+impl ARCState {
+    /// Moves pipelines removed since the last call into the ghost lists and adapts the target to ghost hits
+    fn update(&mut self, data_source: &CleanupDataSource, capacity: usize, recent_count: usize) {
+        for (timestamp, id) in std::mem::take(&mut self.removals) {
+            let was_accessed = matches!(
+                data_source.accesses(&id).and_then(|a| a.first()),
+                Some(first_access) if *first_access <= timestamp
+            );
+
+            if was_accessed {
+                self.frequent_ghosts.insert(timestamp, id);
+            } else {
+                self.recent_ghosts.insert(timestamp, id);
+            }
+        }
+
+        // Ghost lists together remember at most as many pipelines as are stored
+        while recent_count + self.recent_ghosts.len() > capacity {
+            self.recent_ghosts.remove_oldest();
+        }
+        while self.recent_ghosts.len() + self.frequent_ghosts.len() > capacity {
+            self.frequent_ghosts.remove_oldest();
+        }
+
+        for id in std::mem::take(&mut self.misses) {
+            let recent = self.recent_ghosts.len() as f64;
+            let frequent = self.frequent_ghosts.len() as f64;
+
+            if self.recent_ghosts.remove(id) {
+                self.target = (self.target + (frequent / recent).max(1.0)).min(capacity as f64);
+            } else if self.frequent_ghosts.remove(id) {
+                self.target = (self.target - (recent / frequent).max(1.0)).max(0.0);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CleanupAlgorithm for ARCAlgorithm {
+    async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> PipelineID {
+        let mut state = self.state.lock().await;
+
+        let capacity = data_source.pipeline_ids().len();
+        let frequent = data_source.pipelines_by_last_access();
+        let recent_count = capacity - frequent.len();
+
+        state.update(data_source, capacity, recent_count);
+
+        let least_recently_used = frequent.iter().next().map(|(_, id)| *id);
+        let oldest_unaccessed = data_source.oldest_unaccessed_pipeline();
+
+        if recent_count as f64 > state.target.min(capacity as f64) {
+            oldest_unaccessed.or(least_recently_used).unwrap()
+        } else {
+            least_recently_used.or(oldest_unaccessed).unwrap()
+        }
+    }
+
+    async fn observe<'a>(&self, change: StateChange<'a>, _data_source: &DataSource) {
+        match change {
+            StateChange::Removed { id, timestamp } => {
+                self.state.lock().await.removals.push((timestamp, id))
+            }
+            StateChange::Missed(id) => self.state.lock().await.misses.push(id),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use bytesize::ByteSize;
+use std::collections::{BTreeSet, HashMap};
+
+use super::priority::Priority;
+use crate::implementation::{
+    CleanupAlgorithm, CleanupDataSource, DataSource, PipelineID, StateChange,
+};
+
+#[derive(Default)]
+struct GDSFState {
+    /// Priority of the most recently evicted pipeline (`L` in the paper)
+    inflation: f64,
+    /// Priority of each evictable pipeline alongside the frequency and stored size it has been computed for
+    priorities: HashMap<PipelineID, (Priority, usize, ByteSize)>,
+    /// Evictable pipelines ordered by their priority, ties are ordered by pipeline ID
+    order: BTreeSet<(Priority, PipelineID)>,
+}
+
+/// GreedyDual-Size-Frequency (Cherkasova, 1998) evicts the pipeline with the lowest priority
+/// `L + frequency * cost / size`. The frequency counts storing the pipeline as its first reference, the
+/// cost is the duration it takes to re-run the pipeline (at least one second) and the size only includes
+/// artifacts which have not been evicted yet. Priorities are only updated when a pipeline is referenced or
+/// its size changes, while `L` is raised to the priority of each evicted pipeline so that pipelines which
+/// have not been referenced for a long time eventually age out.
+#[derive(Default)]
+pub struct GDSFAlgorithm {
+    state: Mutex<GDSFState>,
+}
+
+#[async_trait]
+impl CleanupAlgorithm for GDSFAlgorithm {
+    async fn select_pipeline<'a>(&self, _data_source: &CleanupDataSource<'a>) -> PipelineID {
+        let mut state = self.state.lock().await;
+        let (priority, id) = *state.order.iter().next().unwrap();
+
+        state.inflation = priority.0;
+
+        id
+    }
+
+    async fn observe<'a>(&self, change: StateChange<'a>, data_source: &DataSource) {
+        let mut state = self.state.lock().await;
+
+        match change {
+            StateChange::Indexed {
+                id, size, accesses, ..
+            } => {
+                let frequency = 1 + accesses.len();
+                let previous = state.priorities.get(&id).copied();
+
+                if let Some((priority, computed_for, computed_size)) = previous {
+                    if computed_for == frequency && computed_size == size {
+                        return;
+                    }
+
+                    state.order.remove(&(priority, id));
+                }
+
+                let cost = data_source.duration_of_pipeline(id).await.unwrap().max(1);
+                let priority = Priority(
+                    state.inflation + frequency as f64 * cost as f64 / size.as_u64().max(1) as f64,
+                );
+
+                state.priorities.insert(id, (priority, frequency, size));
+                state.order.insert((priority, id));
+            }
+            StateChange::Unindexed(id) => {
+                if let Some((priority, _, _)) = state.priorities.remove(&id) {
+                    state.order.remove(&(priority, id));
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::implementation::PipelineID;
+
+/// Recently evicted pipelines which are no longer stored, ordered by the time of their eviction
+#[derive(Default)]
+pub struct GhostList {
+    entries: BTreeSet<(i64, PipelineID)>,
+    eviction_times: HashMap<PipelineID, i64>,
+}
+
+impl GhostList {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn insert(&mut self, timestamp: i64, id: PipelineID) {
+        self.entries.insert((timestamp, id));
+        self.eviction_times.insert(id, timestamp);
+    }
+
+    pub fn remove(&mut self, id: PipelineID) -> bool {
+        match self.eviction_times.remove(&id) {
+            Some(timestamp) => self.entries.remove(&(timestamp, id)),
+            None => false,
+        }
+    }
+
+    pub fn remove_oldest(&mut self) {
+        if let Some((_, id)) = self.entries.iter().next().copied() {
+            self.remove(id);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use async_trait::async_trait;
+
+use crate::implementation::{CleanupDataSource, JobCleanupAlgorithm, PipelineID};
+
+/// Evicts the largest stored job, regardless of the pipeline it belongs to
+#[derive(Debug)]
+pub struct LargestJobAlgorithm {}
+
+#[async_trait]
+impl JobCleanupAlgorithm for LargestJobAlgorithm {
+    async fn select_job<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+    ) -> Option<(PipelineID, usize)> {
+        let jobs = data_source.jobs_by_size();
+        let (largest_size, _, _) = jobs.iter().next_back()?;
+
+        // Select a job of the oldest of all pipelines with a job of the largest size
+        jobs.range((*largest_size, PipelineID::MIN, 0)..)
+            .next()
+            .map(|(_, id, index)| (*id, *index))
+    }
+}
@@ -0,0 +1,5 @@
+mod largest;
+mod pattern;
+
+pub use largest::LargestJobAlgorithm;
+pub use pattern::JobPatternAlgorithm;
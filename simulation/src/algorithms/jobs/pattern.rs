@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use regex::Regex;
+
+use crate::implementation::{CleanupDataSource, JobCleanupAlgorithm, PipelineID};
+
+/// Evicts the largest stored job whose name matches the pattern, e.g. to drop video or screenshot
+/// jobs before resorting to whole pipelines
+#[derive(Debug)]
+pub struct JobPatternAlgorithm {
+    pattern: Regex,
+}
+
+impl JobPatternAlgorithm {
+    pub fn new(pattern: Regex) -> Self {
+        Self { pattern }
+    }
+}
+
+#[async_trait]
+impl JobCleanupAlgorithm for JobPatternAlgorithm {
+    async fn select_job<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+    ) -> Option<(PipelineID, usize)> {
+        data_source
+            .jobs_by_size()
+            .iter()
+            .rev()
+            .find(|(_, id, index)| {
+                matches!(
+                    data_source.pipeline_jobs(*id).and_then(|jobs| jobs.get(*index)),
+                    Some(job) if self.pattern.is_match(&job.name)
+                )
+            })
+            .map(|(_, id, index)| (*id, *index))
+    }
+}
@@ -1,5 +1,4 @@
 use async_trait::async_trait;
-use bytesize::ByteSize;
 
 use crate::implementation::{CleanupAttemptAlgorithm, CleanupDataSource, PipelineID};
 
@@ -9,19 +8,13 @@ pub struct LargestFirstAlgorithm {}
 #[async_trait]
 impl CleanupAttemptAlgorithm for LargestFirstAlgorithm {
     async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> Option<PipelineID> {
-        let mut largest: Option<(PipelineID, ByteSize)> = None;
+        let pipelines = data_source.pipelines_by_size();
+        let (largest_size, _) = pipelines.iter().next_back()?;
 
-        for id in data_source.pipeline_ids() {
-            let pipeline_size = data_source.pipeline_size(*id).await.unwrap();
-            if let Some((_, current_size)) = largest {
-                if current_size < pipeline_size {
-                    largest = Some((*id, pipeline_size));
-                }
-            } else {
-                largest = Some((*id, pipeline_size));
-            }
-        }
-
-        largest.map(|l| l.0)
+        // Select the oldest of all pipelines with the largest size
+        pipelines
+            .range((*largest_size, PipelineID::MIN)..)
+            .next()
+            .map(|(_, id)| *id)
     }
 }
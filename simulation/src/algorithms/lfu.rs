@@ -0,0 +1,95 @@
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use std::collections::{BTreeSet, HashMap};
+
+use super::priority::Priority;
+use crate::implementation::{
+    CleanupAlgorithm, CleanupDataSource, DataSource, PipelineID, StateChange,
+};
+
+/// Frequency of a pipeline alongside the number of accesses it covers and the latest reference
+type Frequency = (Priority, usize, i64);
+
+#[derive(Default)]
+struct LFUState {
+    /// Time the weights of all accesses are relative to, which keeps the exponents small
+    origin: Option<i64>,
+    frequencies: HashMap<PipelineID, Frequency>,
+    /// Evictable pipelines ordered by their frequency, ties are ordered by their latest reference
+    order: BTreeSet<(Priority, i64, PipelineID)>,
+}
+
+/// Least frequently used with aging: each reference counts for half as much once `half_life` seconds have passed.
+/// Storing a pipeline counts as its first reference, so new pipelines compete with accessed ones instead of
+/// being evicted before they could be accessed. Ties are broken by evicting the least recently referenced one.
+///
+/// As all references age at the same rate, the order of the pipelines only changes when they are accessed.
+/// Frequencies are thus kept as `log2(sum(2^(reference / half_life)))`, updated with each new access.
+pub struct LFUAlgorithm {
+    half_life: i64,
+    state: Mutex<LFUState>,
+}
+
+impl LFUAlgorithm {
+    pub fn new(half_life: i64) -> Self {
+        Self {
+            half_life,
+            state: Mutex::new(LFUState::default()),
+        }
+    }
+}
+
+/// Adds two numbers given by their logarithm to base 2, i.e. `log2(2^a + 2^b)`
+fn add_log2(a: f64, b: f64) -> f64 {
+    let (larger, smaller) = if a > b { (a, b) } else { (b, a) };
+
+    larger + (smaller - larger).exp2().ln_1p() / std::f64::consts::LN_2
+}
+
+#[async_trait]
+impl CleanupAlgorithm for LFUAlgorithm {
+    async fn select_pipeline<'a>(&self, _data_source: &CleanupDataSource<'a>) -> PipelineID {
+        let state = self.state.lock().await;
+
+        state.order.iter().next().map(|(_, _, id)| *id).unwrap()
+    }
+
+    async fn observe<'a>(&self, change: StateChange<'a>, _data_source: &DataSource) {
+        let mut state = self.state.lock().await;
+
+        match change {
+            StateChange::Indexed {
+                id,
+                stored_at,
+                accesses,
+                ..
+            } => {
+                let origin = *state.origin.get_or_insert(stored_at);
+                let exponent = |reference: i64| (reference - origin) as f64 / self.half_life as f64;
+                let (mut frequency, counted, latest_reference) = match state.frequencies.get(&id) {
+                    Some((_, counted, _)) if *counted == accesses.len() => return,
+                    Some(frequency) => *frequency,
+                    None => (Priority(exponent(stored_at)), 0, stored_at),
+                };
+
+                state.order.remove(&(frequency, latest_reference, id));
+
+                for access in &accesses[counted..] {
+                    frequency = Priority(add_log2(frequency.0, exponent(*access)));
+                }
+
+                let latest_reference = accesses.last().copied().unwrap_or(latest_reference);
+                state
+                    .frequencies
+                    .insert(id, (frequency, accesses.len(), latest_reference));
+                state.order.insert((frequency, latest_reference, id));
+            }
+            StateChange::Unindexed(id) => {
+                if let Some((frequency, _, latest_access)) = state.frequencies.remove(&id) {
+                    state.order.remove(&(frequency, latest_access, id));
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -8,17 +8,8 @@ pub struct LRUAlgorithm {}
 #[async_trait]
 impl CleanupAttemptAlgorithm for LRUAlgorithm {
     async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> Option<PipelineID> {
-        let pipelines = data_source.pipeline_ids();
+        let least_recently_used = data_source.pipelines_by_last_access().iter().next();
 
-        let last_accesses = pipelines.iter().filter_map(|id| {
-            data_source
-                .accesses(id)
-                .map(|a| a.last())
-                .flatten()
-                .map(|l| (id, l))
-        });
-        let least_recently_used = last_accesses.min_by(|x, y| x.1.cmp(y.1));
-
-        least_recently_used.map(|(id, _)| *id)
+        least_recently_used.map(|(_, id)| *id)
     }
 }
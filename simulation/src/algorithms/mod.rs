@@ -1,25 +1,43 @@
+mod arc;
 mod fifo;
+mod gdsf;
+mod ghost_list;
 mod largest_first;
+mod lfu;
 mod lifo;
 mod lru;
 mod merged;
 mod mru;
 mod mru_ranged;
+mod optimal;
+mod priority;
 mod random;
 mod smallest_first;
 mod status;
+mod superseded;
+mod two_queue;
 
+mod jobs;
+mod retention;
 mod scoring;
 
+pub use jobs::*;
+pub use retention::*;
 pub use scoring::*;
 
+pub use arc::ARCAlgorithm;
 pub use fifo::FIFOAlgorithm;
+pub use gdsf::GDSFAlgorithm;
 pub use largest_first::LargestFirstAlgorithm;
+pub use lfu::LFUAlgorithm;
 pub use lifo::LIFOAlgorithm;
 pub use lru::LRUAlgorithm;
 pub use merged::BranchMergedAlgorithm;
 pub use mru::MRUAlgorithm;
 pub use mru_ranged::MRURangedAlgorithm;
+pub use optimal::OptimalAlgorithm;
 pub use random::RandomAlgorithm;
 pub use smallest_first::SmallestFirstAlgorithm;
 pub use status::StatusAlgorithm as LayeredStatusAlgorithm;
+pub use superseded::SupersededFirstAlgorithm;
+pub use two_queue::TwoQueueAlgorithm;
@@ -0,0 +1,32 @@
+use async_trait::async_trait;
+
+use crate::implementation::{CleanupAlgorithm, CleanupDataSource, PipelineID};
+
+/// Clairvoyant algorithm (Belady's OPT) which evicts the pipeline whose next access lies furthest in the future.
+/// Since it peeks into the future it can not be implemented in reality but serves as an upper bound for all other algorithms.
+pub struct OptimalAlgorithm {}
+
+#[async_trait]
+impl CleanupAlgorithm for OptimalAlgorithm {
+    async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> PipelineID {
+        let mut furthest: Option<(PipelineID, i64)> = None;
+
+        for id in data_source.pipeline_ids() {
+            match data_source.next_access(*id).await.unwrap() {
+                // Pipelines which will never be accessed again can not be beaten
+                None => return *id,
+                Some(next_access) => {
+                    if let Some((_, current_next_access)) = furthest {
+                        if current_next_access < next_access {
+                            furthest = Some((*id, next_access));
+                        }
+                    } else {
+                        furthest = Some((*id, next_access));
+                    }
+                }
+            }
+        }
+
+        furthest.unwrap().0
+    }
+}
@@ -0,0 +1,19 @@
+use std::cmp::Ordering;
+
+/// Floating point priority which can be stored in ordered collections, it must never be NaN
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Priority(pub f64);
+
+impl Eq for Priority {}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
@@ -0,0 +1,42 @@
+use async_trait::async_trait;
+
+use crate::implementation::{
+    CleanupDataSource, PipelineID, RetentionPolicy, SimulationEvent, SimulationEventKind,
+};
+
+/// Keeps only the newest pipelines of each ref, older ones expire as soon as a new pipeline of the ref is stored
+#[derive(Debug)]
+pub struct KeepLastPolicy {
+    count: usize,
+}
+
+impl KeepLastPolicy {
+    pub fn new(count: usize) -> Self {
+        Self { count }
+    }
+}
+
+#[async_trait]
+impl RetentionPolicy for KeepLastPolicy {
+    async fn expired_pipelines<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+        event: &SimulationEvent,
+    ) -> Vec<PipelineID> {
+        if event.kind != SimulationEventKind::PipelineCreated {
+            return Vec::new();
+        }
+
+        let pipelines = match data_source
+            .pipeline_ref(event.key)
+            .and_then(|r| data_source.pipelines_of_ref(r))
+        {
+            Some(pipelines) => pipelines,
+            None => return Vec::new(),
+        };
+
+        let excess = pipelines.len().saturating_sub(self.count);
+
+        pipelines.iter().take(excess).map(|(_, id)| *id).collect()
+    }
+}
@@ -0,0 +1,32 @@
+use async_trait::async_trait;
+
+use crate::implementation::{CleanupDataSource, PipelineID, RetentionPolicy, SimulationEvent};
+
+/// Expires all pipelines of a branch once a grace period in seconds has passed after it has been merged
+#[derive(Debug)]
+pub struct MergedBranchPolicy {
+    grace_period: i64,
+}
+
+impl MergedBranchPolicy {
+    pub fn new(grace_period: i64) -> Self {
+        Self { grace_period }
+    }
+}
+
+#[async_trait]
+impl RetentionPolicy for MergedBranchPolicy {
+    async fn expired_pipelines<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+        _event: &SimulationEvent,
+    ) -> Vec<PipelineID> {
+        let cutoff = data_source.current_time() - self.grace_period;
+
+        data_source
+            .pipelines_by_merge_time()
+            .range(..=(cutoff, PipelineID::MAX))
+            .map(|(_, id)| *id)
+            .collect()
+    }
+}
@@ -0,0 +1,7 @@
+mod keep_last;
+mod merged;
+mod ttl;
+
+pub use keep_last::KeepLastPolicy;
+pub use merged::MergedBranchPolicy;
+pub use ttl::TTLPolicy;
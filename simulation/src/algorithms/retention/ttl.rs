@@ -0,0 +1,47 @@
+use async_trait::async_trait;
+
+use crate::implementation::{
+    CleanupDataSource, PipelineID, PipelineStatus, RetentionPolicy, SimulationEvent,
+};
+
+/// Expires pipelines once they have been stored for longer than the maximum age in seconds.
+/// If a status is given, only pipelines with that status expire.
+#[derive(Debug)]
+pub struct TTLPolicy {
+    max_age: i64,
+    status: Option<PipelineStatus>,
+}
+
+impl TTLPolicy {
+    pub fn new(max_age: i64, status: Option<PipelineStatus>) -> Self {
+        Self { max_age, status }
+    }
+}
+
+#[async_trait]
+impl RetentionPolicy for TTLPolicy {
+    async fn expired_pipelines<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+        _event: &SimulationEvent,
+    ) -> Vec<PipelineID> {
+        let cutoff = data_source.current_time() - self.max_age;
+        let mut expired = Vec::new();
+
+        for (status, pipelines) in data_source.pipelines_by_storage_time() {
+            if self.status.is_some() && self.status != *status {
+                continue;
+            }
+
+            expired.extend(
+                pipelines
+                    .range(..(cutoff, PipelineID::MIN))
+                    .map(|(_, id)| *id),
+            );
+        }
+
+        // Groups are visited in arbitrary order
+        expired.sort_unstable();
+        expired
+    }
+}
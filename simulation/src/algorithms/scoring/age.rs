@@ -26,41 +26,35 @@ impl Default for AgeAlgorithm {
 
 #[async_trait]
 impl ScoringAlgorithm for AgeAlgorithm {
-    async fn score_pipelines<'a>(
+    async fn score_pipeline<'a>(
         &self,
         data_source: &CleanupDataSource<'a>,
-        pipelines: &Vec<PipelineID>,
-    ) -> Vec<Score> {
-        let mut scores = Vec::with_capacity(pipelines.len());
-
-        for pipeline in pipelines {
-            let score = if let Some(age) = data_source.pipeline_age(*pipeline) {
-                let age = age as f64;
-                let threshold = self.threshold as f64;
-                let percentage = 1.0f64.max(0.0f64.min(age / threshold));
-                // y = -0.5 * (cos(pi * x) - 1)
-                let interpolated = -0.5 * ((PI * percentage).cos() - 1.0);
-
-                (interpolated * (self.score as f64)).round() as Score
-            } else {
-                0
-            };
-
-            scores.push(score);
-
-            // scores.push(
-            //     if data_source
-            //         .pipeline_age(*pipeline)
-            //         .map(|i| i < self.threshold)
-            //         .unwrap_or(false)
-            //     {
-            //         self.score
-            //     } else {
-            //         0
-            //     },
-            // );
+        pipeline: PipelineID,
+    ) -> Score {
+        if let Some(age) = data_source.pipeline_age(pipeline) {
+            let age = age as f64;
+            let threshold = self.threshold as f64;
+            let percentage = 1.0f64.max(0.0f64.min(age / threshold));
+            // y = -0.5 * (cos(pi * x) - 1)
+            let interpolated = -0.5 * ((PI * percentage).cos() - 1.0);
+
+            (interpolated * (self.score as f64)).round() as Score
+        } else {
+            0
         }
 
-        scores
+        // if data_source
+        //     .pipeline_age(pipeline)
+        //     .map(|i| i < self.threshold)
+        //     .unwrap_or(false)
+        // {
+        //     self.score
+        // } else {
+        //     0
+        // }
+    }
+
+    fn dynamic_score_limit(&self) -> Option<Score> {
+        Some(self.score.max(0))
     }
 }
@@ -5,50 +5,127 @@ pub type Score = i32;
 
 #[async_trait]
 pub trait ScoringAlgorithm: Send + Sync {
-    async fn score_pipelines<'a>(
+    async fn score_pipeline<'a>(
         &self,
         data_source: &CleanupDataSource<'a>,
-        pipelines: &Vec<PipelineID>,
-    ) -> Vec<Score>;
+        pipeline: PipelineID,
+    ) -> Score;
+
+    /// Upper bound for scores which change over time (e.g. with the age of a pipeline).
+    /// Components without a bound may only depend on the scoring class of a pipeline, i.e. its status and whether it has been merged.
+    fn dynamic_score_limit(&self) -> Option<Score> {
+        None
+    }
 }
 
 pub struct ScoringAlgorithmManager {
-    algorithms: Vec<Box<dyn ScoringAlgorithm>>,
+    static_algorithms: Vec<Box<dyn ScoringAlgorithm>>,
+    dynamic_algorithms: Vec<Box<dyn ScoringAlgorithm>>,
+    dynamic_score_limit: Score,
 }
 
 impl ScoringAlgorithmManager {
     pub fn new(algorithms: Vec<Box<dyn ScoringAlgorithm>>) -> Self {
-        Self { algorithms }
+        let (dynamic_algorithms, static_algorithms): (Vec<_>, Vec<_>) = algorithms
+            .into_iter()
+            .partition(|a| a.dynamic_score_limit().is_some());
+
+        let dynamic_score_limit = dynamic_algorithms
+            .iter()
+            .filter_map(|a| a.dynamic_score_limit())
+            .sum();
+
+        Self {
+            static_algorithms,
+            dynamic_algorithms,
+            dynamic_score_limit,
+        }
+    }
+
+    async fn score<'a>(
+        algorithms: &[Box<dyn ScoringAlgorithm>],
+        data_source: &CleanupDataSource<'a>,
+        pipeline: PipelineID,
+    ) -> Score {
+        let mut score = 0;
+
+        for algorithm in algorithms {
+            score += algorithm.score_pipeline(data_source, pipeline).await;
+        }
+
+        score
     }
 }
 
 #[async_trait]
 impl CleanupAlgorithm for ScoringAlgorithmManager {
+    /// Selects the pipeline with the highest score, ties are resolved in favour of the oldest pipeline.
+    /// As static scores are shared by all pipelines within a scoring class, only the first pipeline of each class
+    /// has to be considered unless there are dynamic components. Those are evaluated per pipeline until the upper bound is reached.
     async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> PipelineID {
-        let pipeline_ids = data_source
-            .pipeline_ids()
-            .iter()
-            .map(|id| *id)
-            .collect::<Vec<_>>();
+        let mut highscore: Option<(Score, PipelineID)> = None;
+
+        for pipelines in data_source.pipelines_by_scoring_class().values() {
+            let representative = match pipelines.iter().next() {
+                Some(id) => *id,
+                None => continue,
+            };
 
-        // Could be done nicer with iterators, map and such but the async/await makes that annoyingly hard
-        let mut scores = vec![0; pipeline_ids.len()];
-        for algorithm in self.algorithms.iter() {
-            let current_scores = algorithm.score_pipelines(data_source, &pipeline_ids).await;
+            let static_score =
+                Self::score(&self.static_algorithms, data_source, representative).await;
+            let score_limit = static_score + self.dynamic_score_limit;
 
-            for i in 0..scores.len() {
-                scores[i] += current_scores[i];
+            // Neither this nor any other pipeline of this class can beat the current highscore
+            if let Some((score, _)) = highscore {
+                if score_limit < score {
+                    continue;
+                }
             }
-        }
 
-        // Find the first maximum value in the list of scores
-        let mut highscore = (0, scores[0]);
-        for (i, score) in scores.into_iter().skip(1).enumerate() {
-            if highscore.1 < score {
-                highscore = (i, score)
+            let mut candidate = (static_score, representative);
+
+            if !self.dynamic_algorithms.is_empty() {
+                let mut best_score = None;
+
+                for id in pipelines {
+                    let score = static_score
+                        + Self::score(&self.dynamic_algorithms, data_source, *id).await;
+
+                    if best_score < Some(score) {
+                        best_score = Some(score);
+                        candidate = (score, *id);
+                    }
+
+                    if score >= score_limit {
+                        break;
+                    }
+                }
+            }
+
+            let is_better = match highscore {
+                Some((score, id)) => {
+                    score < candidate.0 || (score == candidate.0 && candidate.1 < id)
+                }
+                None => true,
+            };
+            if is_better {
+                highscore = Some(candidate);
             }
         }
 
-        pipeline_ids[highscore.0]
+        match highscore {
+            // Like the previous implementation, this selects the pipeline stored just before the highest scoring one
+            Some((_, id)) => *data_source
+                .pipeline_ids()
+                .range(..id)
+                .next_back()
+                .unwrap_or(&id),
+            // Pipelines with an unknown status are not part of any scoring class
+            None => *data_source
+                .pipeline_ids()
+                .iter()
+                .next()
+                .expect("No pipeline available for eviction"),
+        }
     }
 }
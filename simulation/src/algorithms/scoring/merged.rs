@@ -22,22 +22,15 @@ impl Default for MergedAlgorithm {
 
 #[async_trait]
 impl ScoringAlgorithm for MergedAlgorithm {
-    async fn score_pipelines<'a>(
+    async fn score_pipeline<'a>(
         &self,
         data_source: &CleanupDataSource<'a>,
-        pipelines: &Vec<PipelineID>,
-    ) -> Vec<Score> {
-        let mut scores = Vec::with_capacity(pipelines.len());
-        let merges = data_source.merges();
-
-        for pipeline in pipelines {
-            scores.push(if merges.contains(pipeline) {
-                self.score
-            } else {
-                0
-            });
+        pipeline: PipelineID,
+    ) -> Score {
+        if data_source.merges().contains(&pipeline) {
+            self.score
+        } else {
+            0
         }
-
-        scores
     }
 }
@@ -2,8 +2,12 @@ mod algorithm;
 mod status;
 mod merged;
 mod age;
+mod ref_category;
+mod superseded;
 
 pub use algorithm::{Score, ScoringAlgorithm, ScoringAlgorithmManager};
 pub use status::StatusAlgorithm;
 pub use merged::MergedAlgorithm;
-pub use age::AgeAlgorithm;
\ No newline at end of file
+pub use age::AgeAlgorithm;
+pub use ref_category::RefCategoryAlgorithm;
+pub use superseded::SupersededAlgorithm;
\ No newline at end of file
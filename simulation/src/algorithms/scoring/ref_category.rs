@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+use crate::implementation::{CleanupDataSource, PipelineID};
+
+use super::{Score, ScoringAlgorithm};
+
+/// Scores pipelines by the category of their ref, pipelines of other categories score zero
+pub struct RefCategoryAlgorithm {
+    scores: HashMap<String, Score>,
+}
+
+impl RefCategoryAlgorithm {
+    pub fn new(scores: HashMap<String, Score>) -> Self {
+        Self { scores }
+    }
+}
+
+#[async_trait]
+impl ScoringAlgorithm for RefCategoryAlgorithm {
+    async fn score_pipeline<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+        pipeline: PipelineID,
+    ) -> Score {
+        data_source
+            .ref_category(pipeline)
+            .and_then(|category| self.scores.get(category))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The category is not part of the scoring class, so the component has to be evaluated per pipeline
+    fn dynamic_score_limit(&self) -> Option<Score> {
+        Some(self.scores.values().copied().max().unwrap_or(0).max(0))
+    }
+}
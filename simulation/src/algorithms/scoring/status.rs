@@ -30,28 +30,20 @@ impl Default for StatusAlgorithm {
 
 #[async_trait]
 impl ScoringAlgorithm for StatusAlgorithm {
-    async fn score_pipelines<'a>(
+    async fn score_pipeline<'a>(
         &self,
         data_source: &CleanupDataSource<'a>,
-        pipelines: &Vec<PipelineID>,
-    ) -> Vec<Score> {
-        let mut scores = Vec::with_capacity(pipelines.len());
+        pipeline: PipelineID,
+    ) -> Score {
+        let status = data_source.pipeline_status(pipeline).await.unwrap();
 
-        for pipeline in pipelines {
-            let status = data_source.pipeline_status(*pipeline).await.unwrap();
+        match status {
+            PipelineStatus::Running => self.running,
+            PipelineStatus::Success => self.success,
+            PipelineStatus::Failed => self.failed,
+            PipelineStatus::Cancelled => self.cancelled,
 
-            let score = match status {
-                PipelineStatus::Running => self.running,
-                PipelineStatus::Success => self.success,
-                PipelineStatus::Failed => self.failed,
-                PipelineStatus::Cancelled => self.cancelled,
-
-                _ => 0,
-            };
-
-            scores.push(score);
+            _ => 0,
         }
-
-        scores
     }
 }
@@ -0,0 +1,36 @@
+use async_trait::async_trait;
+
+use crate::implementation::{CleanupDataSource, PipelineID};
+
+use super::{Score, ScoringAlgorithm};
+
+/// Scores pipelines which have been superseded by a newer pipeline of the same ref
+pub struct SupersededAlgorithm {
+    score: Score,
+}
+
+impl SupersededAlgorithm {
+    pub fn new(score: Score) -> Self {
+        Self { score }
+    }
+}
+
+#[async_trait]
+impl ScoringAlgorithm for SupersededAlgorithm {
+    async fn score_pipeline<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+        pipeline: PipelineID,
+    ) -> Score {
+        if data_source.is_superseded(pipeline) {
+            self.score
+        } else {
+            0
+        }
+    }
+
+    /// Pipelines become superseded over time independent of their scoring class
+    fn dynamic_score_limit(&self) -> Option<Score> {
+        Some(self.score.max(0))
+    }
+}
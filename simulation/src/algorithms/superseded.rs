@@ -0,0 +1,19 @@
+use async_trait::async_trait;
+
+use crate::implementation::{CleanupAttemptAlgorithm, CleanupDataSource, PipelineID};
+
+/// Evicts pipelines which have been superseded by a newer pipeline of the same ref, oldest first.
+/// Developers rarely look at any but the latest pipeline of a branch.
+#[derive(Debug)]
+pub struct SupersededFirstAlgorithm {}
+
+#[async_trait]
+impl CleanupAttemptAlgorithm for SupersededFirstAlgorithm {
+    async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> Option<PipelineID> {
+        data_source
+            .superseded_pipelines()
+            .iter()
+            .next()
+            .map(|(_, id)| *id)
+    }
+}
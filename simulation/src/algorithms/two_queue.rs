@@ -0,0 +1,90 @@
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+
+use super::ghost_list::GhostList;
+use crate::implementation::{
+    CleanupAlgorithm, CleanupDataSource, DataSource, PipelineID, StateChange,
+};
+
+/// Fraction of the stored pipelines remembered in `A1out` (`Kout` in the paper, which recommends 50%)
+const GHOST_FRACTION: f64 = 0.5;
+
+#[derive(Default)]
+struct TwoQueueState {
+    /// Ghosts of pipelines evicted from `A1` before they have been accessed (`A1out` in the paper)
+    ghosts: GhostList,
+    /// Pipelines removed from storage since the last eviction alongside the time of their removal
+    removals: Vec<(i64, PipelineID)>,
+}
+
+/// 2Q (Johnson and Shasha, 1994). Stored pipelines which have not been accessed yet form the FIFO queue `A1in`
+/// while accessed ones form the LRU queue `Am`. Pipelines are evicted from `A1in` as long as it holds more than
+/// the given fraction of the stored pipelines, these are remembered in the ghost queue `A1out`.
+/// A missed pipeline found in `A1out` would be admitted to `Am` directly, but since missed pipelines are not
+/// stored again in the simulation, its ghost is only dropped.
+pub struct TwoQueueAlgorithm {
+    /// Fraction of the stored pipelines reserved for `A1in` (`Kin` in the paper)
+    threshold: f64,
+    state: Mutex<TwoQueueState>,
+}
+
+impl TwoQueueAlgorithm {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            state: Mutex::new(TwoQueueState::default()),
+        }
+    }
+}
+
+impl TwoQueueState {
+    /// Remembers the pipelines removed from `A1in` since the last call in `A1out`
+    fn update(&mut self, data_source: &CleanupDataSource, stored_count: usize) {
+        for (timestamp, id) in std::mem::take(&mut self.removals) {
+            let was_accessed = matches!(
+                data_source.accesses(&id).and_then(|a| a.first()),
+                Some(first_access) if *first_access <= timestamp
+            );
+
+            if !was_accessed {
+                self.ghosts.insert(timestamp, id);
+            }
+        }
+
+        while self.ghosts.len() as f64 > GHOST_FRACTION * stored_count as f64 {
+            self.ghosts.remove_oldest();
+        }
+    }
+}
+
+#[async_trait]
+impl CleanupAlgorithm for TwoQueueAlgorithm {
+    async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> PipelineID {
+        let stored_count = data_source.pipeline_ids().len();
+        let accessed = data_source.pipelines_by_last_access();
+        let unaccessed_count = stored_count - accessed.len();
+
+        self.state.lock().await.update(data_source, stored_count);
+
+        let least_recently_used = accessed.iter().next().map(|(_, id)| *id);
+        let oldest_unaccessed = data_source.oldest_unaccessed_pipeline();
+
+        if unaccessed_count as f64 > self.threshold * stored_count as f64 {
+            oldest_unaccessed.or(least_recently_used).unwrap()
+        } else {
+            least_recently_used.or(oldest_unaccessed).unwrap()
+        }
+    }
+
+    async fn observe<'a>(&self, change: StateChange<'a>, _data_source: &DataSource) {
+        match change {
+            StateChange::Removed { id, timestamp } => {
+                self.state.lock().await.removals.push((timestamp, id))
+            }
+            StateChange::Missed(id) => {
+                self.state.lock().await.ghosts.remove(id);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,648 @@
+use anyhow::{anyhow, bail, Result};
+use bytesize::ByteSize;
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    path::PathBuf,
+};
+
+use crate::{
+    algorithms::*,
+    implementation::{
+        deserialize_regex, deserialize_status, CleanupAlgorithm, CleanupAttemptAlgorithm,
+        CleanupRules, FallbackCleanupAlgorithm, JobCleanupAlgorithm, PipelineStatus,
+        ProtectionRule, RetentionPolicy,
+    },
+    opts::storage_limit_name,
+    SimulationSpecification,
+};
+
+/// Algorithms which may decline to select a pipeline and thus have to be followed by a fallback algorithm
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "algorithm")]
+pub enum AttemptAlgorithmSpecification {
+    #[serde(rename = "MERGED")]
+    Merged,
+    #[serde(rename = "LRU")]
+    Lru,
+    /// Plain MRU if no range is given, otherwise only pipelines with at least `range` accesses are considered
+    #[serde(rename = "MRU")]
+    Mru { range: Option<usize> },
+    #[serde(rename = "LF")]
+    LargestFirst,
+    #[serde(rename = "SF")]
+    SmallestFirst,
+    #[serde(rename = "STATUS")]
+    Status,
+    /// Evicts pipelines superseded by a newer pipeline of the same ref, oldest first
+    #[serde(rename = "SUPERSEDED")]
+    Superseded,
+}
+
+impl AttemptAlgorithmSpecification {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "MERGED" => Some(Self::Merged),
+            "LRU" => Some(Self::Lru),
+            "MRU" => Some(Self::Mru { range: None }),
+            "LF" => Some(Self::LargestFirst),
+            "SF" => Some(Self::SmallestFirst),
+            "STATUS" => Some(Self::Status),
+            "SUPERSEDED" => Some(Self::Superseded),
+            _ => name
+                .strip_prefix("MRU.")
+                .and_then(|range| range.parse().ok())
+                .map(|range| Self::Mru { range: Some(range) }),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::Merged => "MERGED".to_owned(),
+            Self::Lru => "LRU".to_owned(),
+            Self::Mru { range: None } => "MRU".to_owned(),
+            Self::Mru { range: Some(range) } => format!("MRU.{}", range),
+            Self::LargestFirst => "LF".to_owned(),
+            Self::SmallestFirst => "SF".to_owned(),
+            Self::Status => "STATUS".to_owned(),
+            Self::Superseded => "SUPERSEDED".to_owned(),
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn CleanupAttemptAlgorithm> {
+        match self {
+            Self::Merged => Box::new(BranchMergedAlgorithm {}),
+            Self::Lru => Box::new(LRUAlgorithm {}),
+            Self::Mru { range: None } => Box::new(MRUAlgorithm {}),
+            Self::Mru { range: Some(range) } => Box::new(MRURangedAlgorithm::new(*range)),
+            Self::LargestFirst => Box::new(LargestFirstAlgorithm {}),
+            Self::SmallestFirst => Box::new(SmallestFirstAlgorithm {}),
+            Self::Status => Box::new(LayeredStatusAlgorithm {}),
+            Self::Superseded => Box::new(SupersededFirstAlgorithm {}),
+        }
+    }
+}
+
+/// Algorithms which evict the artifacts of individual jobs, they require a job-level simulation
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "algorithm")]
+pub enum JobAlgorithmSpecification {
+    #[serde(rename = "LJ")]
+    LargestJob,
+    /// Evicts the largest job whose name (`<environment>:<testSuite>`) matches the regular expression
+    #[serde(rename = "JOB")]
+    Pattern {
+        #[serde(deserialize_with = "deserialize_regex")]
+        pattern: Regex,
+    },
+}
+
+impl JobAlgorithmSpecification {
+    /// Parses names like `LJ` or `JOB.video|screenshot`
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name == "LJ" {
+            return Some(Self::LargestJob);
+        }
+
+        name.strip_prefix("JOB.")
+            .and_then(|pattern| Regex::new(pattern).ok())
+            .map(|pattern| Self::Pattern { pattern })
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::LargestJob => "LJ".to_owned(),
+            Self::Pattern { pattern } => format!("JOB.{}", pattern),
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn JobCleanupAlgorithm> {
+        match self {
+            Self::LargestJob => Box::new(LargestJobAlgorithm {}),
+            Self::Pattern { pattern } => Box::new(JobPatternAlgorithm::new(pattern.clone())),
+        }
+    }
+}
+
+/// Fraction of the stored pipelines 2Q reserves for pipelines which have not been accessed yet
+const DEFAULT_2Q_THRESHOLD: f64 = 0.25;
+
+/// Seconds after which an access counts for half as much in LFU
+const DEFAULT_LFU_HALF_LIFE: i64 = 60 * 60 * 24;
+
+/// Algorithms which always select a pipeline and can thus be used at the end of a chain
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "algorithm")]
+pub enum FallbackAlgorithmSpecification {
+    #[serde(rename = "RAND")]
+    Random,
+    #[serde(rename = "LIFO")]
+    Lifo,
+    #[serde(rename = "FIFO")]
+    Fifo,
+    #[serde(rename = "OPT")]
+    Optimal,
+    #[serde(rename = "ARC")]
+    Arc,
+    /// 2Q, `threshold` is the fraction of stored pipelines reserved for unaccessed ones (defaults to 0.25)
+    #[serde(rename = "2Q")]
+    TwoQueue { threshold: Option<f64> },
+    /// LFU with aging, accesses count for half as much after `half_life` seconds (defaults to one day)
+    #[serde(rename = "LFU")]
+    Lfu { half_life: Option<i64> },
+    /// GreedyDual-Size-Frequency, weighs access frequency and re-run duration against size
+    #[serde(rename = "GDSF")]
+    Gdsf,
+    #[serde(rename = "SCORE")]
+    Score {
+        /// Optional name to distinguish between differently weighted scoring algorithms
+        name: Option<String>,
+        components: Vec<ScoringComponentSpecification>,
+    },
+}
+
+impl FallbackAlgorithmSpecification {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "RAND" => Some(Self::Random),
+            "LIFO" => Some(Self::Lifo),
+            "FIFO" => Some(Self::Fifo),
+            "OPT" => Some(Self::Optimal),
+            "ARC" => Some(Self::Arc),
+            "2Q" => Some(Self::TwoQueue { threshold: None }),
+            "LFU" => Some(Self::Lfu { half_life: None }),
+            "GDSF" => Some(Self::Gdsf),
+            "SCORE.DEFAULT" => Some(Self::Score {
+                name: Some(name.to_owned()),
+                components: vec![
+                    ScoringComponentSpecification::Status {
+                        running: -1,
+                        success: 10,
+                        failed: 1,
+                        cancelled: 3,
+                    },
+                    ScoringComponentSpecification::Merged { score: 5 },
+                    ScoringComponentSpecification::Age {
+                        threshold: 60 * 60 * 24 * 2,
+                        score: 30,
+                    },
+                ],
+            }),
+            "SCORE" => Some(Self::Score {
+                name: None,
+                components: vec![
+                    ScoringComponentSpecification::Status {
+                        running: 0,
+                        success: 45,
+                        failed: -5,
+                        cancelled: 0,
+                    },
+                    ScoringComponentSpecification::Merged { score: 30 },
+                    ScoringComponentSpecification::Age {
+                        threshold: 60 * 60 * 24 * 3,
+                        score: 50,
+                    },
+                ],
+            }),
+            _ => {
+                if let Some(percentage) = name.strip_prefix("2Q.") {
+                    return percentage
+                        .parse::<u8>()
+                        .ok()
+                        .map(|percentage| Self::TwoQueue {
+                            threshold: Some(f64::from(percentage) / 100.0),
+                        });
+                }
+
+                name.strip_prefix("LFU.")
+                    .and_then(parse_duration)
+                    .map(|half_life| Self::Lfu {
+                        half_life: Some(half_life),
+                    })
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::Random => "RAND".to_owned(),
+            Self::Lifo => "LIFO".to_owned(),
+            Self::Fifo => "FIFO".to_owned(),
+            Self::Optimal => "OPT".to_owned(),
+            Self::Arc => "ARC".to_owned(),
+            Self::TwoQueue { threshold: None } => "2Q".to_owned(),
+            Self::TwoQueue {
+                threshold: Some(threshold),
+            } => format!("2Q.{}", (threshold * 100.0).round()),
+            Self::Lfu { half_life: None } => "LFU".to_owned(),
+            Self::Lfu {
+                half_life: Some(half_life),
+            } => format!("LFU.{}", format_duration(*half_life)),
+            Self::Gdsf => "GDSF".to_owned(),
+            Self::Score { name, .. } => name.clone().unwrap_or_else(|| "SCORE".to_owned()),
+        }
+    }
+
+    pub fn build(&self, seed: u64) -> Box<dyn CleanupAlgorithm> {
+        match self {
+            Self::Random => Box::new(RandomAlgorithm::new(seed)),
+            Self::Lifo => Box::new(LIFOAlgorithm {}),
+            Self::Fifo => Box::new(FIFOAlgorithm {}),
+            Self::Optimal => Box::new(OptimalAlgorithm {}),
+            Self::Arc => Box::new(ARCAlgorithm::default()),
+            Self::TwoQueue { threshold } => Box::new(TwoQueueAlgorithm::new(
+                threshold.unwrap_or(DEFAULT_2Q_THRESHOLD),
+            )),
+            Self::Lfu { half_life } => Box::new(LFUAlgorithm::new(
+                half_life.unwrap_or(DEFAULT_LFU_HALF_LIFE),
+            )),
+            Self::Gdsf => Box::new(GDSFAlgorithm::default()),
+            Self::Score { components, .. } => Box::new(ScoringAlgorithmManager::new(
+                components.iter().map(|c| c.build()).collect(),
+            )),
+        }
+    }
+}
+
+/// Weighted components of the `SCORE` algorithm
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "component")]
+pub enum ScoringComponentSpecification {
+    #[serde(rename = "STATUS")]
+    Status {
+        running: Score,
+        success: Score,
+        failed: Score,
+        cancelled: Score,
+    },
+    #[serde(rename = "MERGED")]
+    Merged { score: Score },
+    /// Score is interpolated from zero to `score` over the first `threshold` seconds of a pipelines lifetime
+    #[serde(rename = "AGE")]
+    Age { threshold: i64, score: Score },
+    /// Score of pipelines superseded by a newer pipeline of the same ref
+    #[serde(rename = "SUPERSEDED")]
+    Superseded { score: Score },
+    /// Scores by the category of the ref of a pipeline (e.g. `{ premaster = 40, master = -20 }`)
+    #[serde(rename = "REF")]
+    RefCategory { scores: HashMap<String, Score> },
+}
+
+impl ScoringComponentSpecification {
+    pub fn build(&self) -> Box<dyn ScoringAlgorithm> {
+        match *self {
+            Self::Status {
+                running,
+                success,
+                failed,
+                cancelled,
+            } => Box::new(StatusAlgorithm::new(running, success, failed, cancelled)),
+            Self::Merged { score } => Box::new(MergedAlgorithm::new(score)),
+            Self::Age { threshold, score } => Box::new(AgeAlgorithm::new(threshold, score)),
+            Self::Superseded { score } => Box::new(SupersededAlgorithm::new(score)),
+            Self::RefCategory { ref scores } => Box::new(RefCategoryAlgorithm::new(scores.clone())),
+        }
+    }
+}
+
+/// Units accepted in durations, e.g. `2d` or `12h`
+const DURATION_UNITS: [(char, i64); 5] = [
+    ('w', 60 * 60 * 24 * 7),
+    ('d', 60 * 60 * 24),
+    ('h', 60 * 60),
+    ('m', 60),
+    ('s', 1),
+];
+
+/// Parses a duration with an optional unit suffix into seconds, rejecting negative durations and those which overflow
+fn parse_duration(duration: &str) -> Option<i64> {
+    let (value, seconds) = DURATION_UNITS
+        .iter()
+        .find_map(|(unit, seconds)| Some((duration.strip_suffix(*unit)?, *seconds)))
+        .unwrap_or((duration, 1));
+
+    value
+        .parse::<i64>()
+        .ok()
+        .filter(|value| *value >= 0)?
+        .checked_mul(seconds)
+}
+
+/// Formats seconds using the largest unit that represents the duration exactly
+fn format_duration(duration: i64) -> String {
+    for (unit, seconds) in DURATION_UNITS.iter() {
+        if duration != 0 && duration % seconds == 0 {
+            return format!("{}{}", duration / seconds, unit);
+        }
+    }
+
+    format!("{}s", duration)
+}
+
+/// Policies which expire pipelines after every event, regardless of whether the storage limit has been exceeded
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "policy")]
+pub enum RetentionPolicySpecification {
+    /// Expires pipelines stored for longer than `max_age` seconds, optionally only those with the given status
+    #[serde(rename = "TTL")]
+    Ttl {
+        max_age: i64,
+        #[serde(default, deserialize_with = "deserialize_status")]
+        status: Option<PipelineStatus>,
+    },
+    /// Keeps only the newest `count` pipelines of each ref
+    #[serde(rename = "KEEP")]
+    KeepLast { count: usize },
+    /// Expires pipelines of merged branches once `grace_period` seconds have passed since the merge
+    #[serde(rename = "MERGED")]
+    MergedBranch { grace_period: i64 },
+}
+
+impl RetentionPolicySpecification {
+    /// Parses names like `TTL.2d`, `TTL.failed.7d`, `KEEP.3` or `MERGED.6h`
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(count) = name.strip_prefix("KEEP.") {
+            return count.parse().ok().map(|count| Self::KeepLast { count });
+        }
+
+        if let Some(grace_period) = name.strip_prefix("MERGED.") {
+            return parse_duration(grace_period)
+                .map(|grace_period| Self::MergedBranch { grace_period });
+        }
+
+        let parameters = name.strip_prefix("TTL.")?;
+        let (status, max_age) = match parameters.split_once('.') {
+            Some((status, max_age)) => (Some(status.parse().ok()?), max_age),
+            None => (None, parameters),
+        };
+
+        Some(Self::Ttl {
+            max_age: parse_duration(max_age)?,
+            status,
+        })
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::Ttl {
+                max_age,
+                status: None,
+            } => format!("TTL.{}", format_duration(*max_age)),
+            Self::Ttl {
+                max_age,
+                status: Some(status),
+            } => format!("TTL.{}.{}", status, format_duration(*max_age)),
+            Self::KeepLast { count } => format!("KEEP.{}", count),
+            Self::MergedBranch { grace_period } => {
+                format!("MERGED.{}", format_duration(*grace_period))
+            }
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn RetentionPolicy> {
+        match *self {
+            Self::Ttl { max_age, status } => Box::new(TTLPolicy::new(max_age, status)),
+            Self::KeepLast { count } => Box::new(KeepLastPolicy::new(count)),
+            Self::MergedBranch { grace_period } => Box::new(MergedBranchPolicy::new(grace_period)),
+        }
+    }
+}
+
+/// Chain of attempt algorithms that are consulted in order before resorting to the fallback algorithm.
+/// Job algorithms precede the attempt algorithms and may only be used in job-level simulations.
+/// Retention policies are applied after every event in addition to the chain.
+/// Pipelines matching any of the protection rules are neither evicted nor expired.
+#[derive(Deserialize, Clone, Debug)]
+pub struct AlgorithmChain {
+    #[serde(default)]
+    pub protect: Vec<ProtectionRule>,
+    #[serde(default)]
+    pub retention: Vec<RetentionPolicySpecification>,
+    #[serde(default)]
+    pub jobs: Vec<JobAlgorithmSpecification>,
+    #[serde(default)]
+    pub attempts: Vec<AttemptAlgorithmSpecification>,
+    pub fallback: FallbackAlgorithmSpecification,
+}
+
+impl AlgorithmChain {
+    /// Parses a list of algorithm names where all but the last have to be attempt algorithms, job algorithms or retention policies (e.g. `["TTL.7d", "MERGED", "LRU", "FIFO"]`)
+    pub fn from_names(names: &[String]) -> Result<Self> {
+        let (fallback, names) = names
+            .split_last()
+            .ok_or_else(|| anyhow!("You must provide at least one algorithm"))?;
+
+        let mut retention = Vec::new();
+        let mut jobs = Vec::new();
+        let mut attempts = Vec::new();
+
+        for name in names {
+            if let Some(policy) = RetentionPolicySpecification::from_name(name) {
+                retention.push(policy);
+            } else if let Some(algorithm) = JobAlgorithmSpecification::from_name(name) {
+                jobs.push(algorithm);
+            } else {
+                attempts.push(
+                    AttemptAlgorithmSpecification::from_name(name)
+                        .ok_or_else(|| anyhow!("Algorithm '{}' not found!", name))?,
+                );
+            }
+        }
+
+        let fallback = FallbackAlgorithmSpecification::from_name(fallback)
+            .ok_or_else(|| anyhow!("Fallback algorithm '{}' not found!", fallback))?;
+
+        Ok(Self {
+            protect: Vec::new(),
+            retention,
+            jobs,
+            attempts,
+            fallback,
+        })
+    }
+
+    /// Parses a dash-joined chain of algorithm names (e.g. `MERGED-LRU-FIFO`)
+    pub fn from_definition(definition: &str) -> Result<Self> {
+        Self::from_names(
+            &definition
+                .split('-')
+                .map(|s| s.to_owned())
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Names of all algorithms in the chain starting with the job algorithms and ending with the fallback algorithm
+    pub fn member_names(&self) -> Vec<String> {
+        self.jobs
+            .iter()
+            .map(|j| j.name())
+            .chain(self.attempts.iter().map(|a| a.name()))
+            .chain(std::iter::once(self.fallback.name()))
+            .collect()
+    }
+
+    pub fn retention_policy_names(&self) -> Vec<String> {
+        self.retention.iter().map(|r| r.name()).collect()
+    }
+
+    pub fn name(&self) -> String {
+        let mut names = self
+            .protect
+            .iter()
+            .map(|rule| format!("PROTECT.{}", rule.name))
+            .collect::<Vec<_>>();
+        names.extend(self.retention_policy_names());
+        names.extend(self.member_names());
+        names.join("-")
+    }
+
+    pub fn build(&self, seed: u64) -> Box<dyn CleanupAlgorithm> {
+        Box::new(
+            FallbackCleanupAlgorithm::new(
+                self.attempts
+                    .iter()
+                    .map(|a| (a.name(), a.build()))
+                    .collect(),
+                (self.fallback.name(), self.fallback.build(seed)),
+            )
+            .with_job_algorithms(self.jobs.iter().map(|j| (j.name(), j.build())).collect()),
+        )
+    }
+
+    /// Retention policies and protection rules of the chain, which apply in addition to the algorithms built by `build`
+    pub fn build_rules(&self) -> CleanupRules {
+        CleanupRules::new(
+            self.retention
+                .iter()
+                .map(|r| (r.name(), r.build()))
+                .collect(),
+            self.protect.clone(),
+        )
+    }
+}
+
+/// Single simulation run definition within an experiment file
+#[derive(Deserialize, Clone, Debug)]
+pub struct RunConfig {
+    /// Name of the run, used as the name of the output file. Defaults to the algorithm chain.
+    pub name: Option<String>,
+    /// Size limits for the simulated disk in GB, the run is simulated once for each limit
+    pub storage_limits: Vec<u64>,
+    #[serde(flatten)]
+    pub algorithms: AlgorithmChain,
+}
+
+impl RunConfig {
+    pub fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.algorithms.name())
+    }
+}
+
+/// Experiment file describing a set of simulation runs
+///
+/// ```toml
+/// seed = 42
+///
+/// [[run]]
+/// storage_limits = [256, 512]
+/// attempts = [{ algorithm = "MERGED" }, { algorithm = "MRU", range = 8 }]
+/// fallback = { algorithm = "FIFO" }
+///
+/// [[run]]
+/// storage_limits = [512]
+/// retention = [
+///     { policy = "TTL", max_age = 172800, status = "success" },
+///     { policy = "TTL", max_age = 604800, status = "failed" },
+///     { policy = "KEEP", count = 3 },
+///     { policy = "MERGED", grace_period = 21600 },
+/// ]
+/// fallback = { algorithm = "FIFO" }
+///
+/// [[run]]
+/// storage_limits = [512]
+/// protect = [
+///     { name = "releases", ref = "^release/" },
+///     { name = "master", ref = "^(master|main)$", latest = 1 },
+///     { name = "recent", status = "success", max_age = 86400 },
+///     { name = "pinned", pipelines = [1234, 5678] },
+/// ]
+/// attempts = [{ algorithm = "LRU" }]
+/// fallback = { algorithm = "FIFO" }
+///
+/// [[run]]
+/// storage_limits = [512]
+/// jobs = [{ algorithm = "JOB", pattern = "video|screenshot" }, { algorithm = "LJ" }]
+/// fallback = { algorithm = "FIFO" }
+///
+/// [[run]]
+/// name = "SCORE-status-only"
+/// storage_limits = [512]
+/// fallback = { algorithm = "SCORE", components = [
+///     { component = "STATUS", running = 0, success = 45, failed = -5, cancelled = 0 },
+/// ] }
+/// ```
+#[derive(Deserialize, Clone, Debug)]
+pub struct ExperimentConfig {
+    /// Overrides the seed passed on the command line
+    pub seed: Option<u64>,
+    #[serde(rename = "run", default)]
+    pub runs: Vec<RunConfig>,
+}
+
+impl ExperimentConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let config: Self = toml::from_str(&content)?;
+
+        if config.runs.is_empty() {
+            bail!(
+                "Experiment file {} does not define any runs",
+                path.display()
+            );
+        }
+
+        // Runs with the same name and storage limit would overwrite each others output
+        let mut outputs = HashSet::new();
+        for run in config.runs.iter() {
+            for limit in run.storage_limits.iter() {
+                if !outputs.insert((run.name(), *limit)) {
+                    bail!(
+                        "Experiment file {} defines multiple runs named '{}' with a storage limit of {} GB, use `name` to distinguish them",
+                        path.display(),
+                        run.name(),
+                        limit
+                    );
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn specifications(self, output_folder: PathBuf) -> Vec<SimulationSpecification> {
+        let mut specifications = Vec::new();
+
+        for run in self.runs {
+            let run_name = run.name();
+
+            for limit in run.storage_limits.iter() {
+                let storage_limit = ByteSize::gb(*limit);
+                let limit_name = storage_limit_name(storage_limit);
+
+                specifications.push(SimulationSpecification {
+                    name: format!("{}-{}", run_name, limit_name),
+                    algorithms: run.algorithms.clone(),
+                    storage_limit,
+                    output_path: output_folder
+                        .join(&limit_name)
+                        .join(format!("{}.csv", run_name)),
+                });
+            }
+        }
+
+        specifications
+    }
+}
@@ -1,11 +1,56 @@
-use async_std::sync::Mutex;
 use async_trait::async_trait;
+use bytesize::ByteSize;
 
-use super::{CleanupDataSource, PipelineID};
+use super::{
+    data_source::{DataSource, SimulationEvent},
+    CleanupDataSource, PipelineID, ProtectionRule,
+};
+
+/// Changes of the simulation state which algorithms maintaining their own indices are notified about.
+/// Pipelines are evictable while they are stored and not protected.
+#[derive(Clone, Copy, Debug)]
+pub enum StateChange<'a> {
+    /// The pipeline became evictable, or the size of its stored artifacts or its accesses changed while it is evictable
+    Indexed {
+        id: PipelineID,
+        size: ByteSize,
+        /// Time at which the pipeline has been stored
+        stored_at: i64,
+        accesses: &'a [i64],
+    },
+    /// The pipeline is no longer evictable because it has been removed or protected
+    Unindexed(PipelineID),
+    /// The pipeline has been removed from storage, regardless of whether it has been evicted or expired
+    Removed { id: PipelineID, timestamp: i64 },
+    /// An access missed the artifacts of the pipeline
+    Missed(PipelineID),
+}
 
 #[async_trait]
 pub trait CleanupAlgorithm: Send + Sync {
     async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> PipelineID;
+
+    /// Selects the artifacts to evict alongside the index of the member within `member_names` that selected them
+    async fn select_eviction<'a>(&self, data_source: &CleanupDataSource<'a>) -> (Eviction, usize) {
+        (
+            Eviction::Pipeline(self.select_pipeline(data_source).await),
+            0,
+        )
+    }
+
+    /// Names of the members evictions are attributed to, algorithms which are not composed of
+    /// other algorithms consist of a single unnamed member
+    fn member_names(&self) -> Vec<String> {
+        vec![String::new()]
+    }
+
+    /// Whether the algorithm evicts the artifacts of individual jobs, which requires a job-level simulation
+    fn evicts_jobs(&self) -> bool {
+        false
+    }
+
+    /// Called for every change of the simulation state in the order they happen
+    async fn observe<'a>(&self, _change: StateChange<'a>, _data_source: &DataSource) {}
 }
 
 #[async_trait]
@@ -13,44 +58,171 @@ pub trait CleanupAttemptAlgorithm: std::fmt::Debug + Send + Sync {
     async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> Option<PipelineID>;
 }
 
+/// Algorithms which evict the artifacts of individual jobs, only available in job-level simulations
+#[async_trait]
+pub trait JobCleanupAlgorithm: std::fmt::Debug + Send + Sync {
+    /// Selects a stored job given by its pipeline and its index within the jobs of the pipeline
+    async fn select_job<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+    ) -> Option<(PipelineID, usize)>;
+}
+
+/// Artifacts selected for eviction by the algorithm chain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eviction {
+    /// All remaining artifacts of the pipeline
+    Pipeline(PipelineID),
+    /// Artifacts of the job with the given index within the jobs of the pipeline
+    Job(PipelineID, usize),
+}
+
+/// Policies which expire pipelines after every event, regardless of whether the storage limit has been exceeded
+#[async_trait]
+pub trait RetentionPolicy: std::fmt::Debug + Send + Sync {
+    /// Stored pipelines which have expired after the given event has been processed.
+    /// Policies may use the event to react to specific events, e.g. merges of a branch.
+    async fn expired_pipelines<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+        event: &SimulationEvent,
+    ) -> Vec<PipelineID>;
+}
+
+/// Retention policies and protection rules which apply in addition to the cleanup algorithm
+#[derive(Default)]
+pub struct CleanupRules {
+    retention_policies: Vec<(String, Box<dyn RetentionPolicy>)>,
+    protection_rules: Vec<ProtectionRule>,
+}
+
+impl CleanupRules {
+    pub fn new(
+        retention_policies: Vec<(String, Box<dyn RetentionPolicy>)>,
+        protection_rules: Vec<ProtectionRule>,
+    ) -> Self {
+        Self {
+            retention_policies,
+            protection_rules,
+        }
+    }
+
+    pub fn retention_policy_name(&self, index: usize) -> &str {
+        &self.retention_policies[index].0
+    }
+
+    pub fn protection_rules(&self) -> &[ProtectionRule] {
+        &self.protection_rules
+    }
+
+    /// Limits of the protection rules which only protect the newest pipelines of each ref
+    pub fn latest_limits(&self) -> impl Iterator<Item = usize> + '_ {
+        self.protection_rules.iter().filter_map(|rule| rule.latest)
+    }
+
+    /// Pipelines expired by any of the retention policies alongside the index of the policy that expired them.
+    /// A pipeline may be returned by multiple policies.
+    pub async fn expired_pipelines<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+        event: &SimulationEvent,
+    ) -> Vec<(PipelineID, usize)> {
+        let mut expired = Vec::new();
+
+        for (i, (_, policy)) in self.retention_policies.iter().enumerate() {
+            for id in policy.expired_pipelines(data_source, event).await {
+                expired.push((id, i));
+            }
+        }
+
+        expired
+    }
+}
+
+/// Chain of cleanup algorithms, each member is given alongside its name
 pub struct FallbackCleanupAlgorithm {
-    algorithms: Vec<Box<dyn CleanupAttemptAlgorithm>>,
-    fallback: Box<dyn CleanupAlgorithm>,
-    total_count: Mutex<usize>,
-    fallback_count: Mutex<usize>,
+    job_algorithms: Vec<(String, Box<dyn JobCleanupAlgorithm>)>,
+    algorithms: Vec<(String, Box<dyn CleanupAttemptAlgorithm>)>,
+    fallback: (String, Box<dyn CleanupAlgorithm>),
 }
 
 impl FallbackCleanupAlgorithm {
     pub fn new(
-        algorithms: Vec<Box<dyn CleanupAttemptAlgorithm>>,
-        fallback: Box<dyn CleanupAlgorithm>,
+        algorithms: Vec<(String, Box<dyn CleanupAttemptAlgorithm>)>,
+        fallback: (String, Box<dyn CleanupAlgorithm>),
     ) -> Self {
         Self {
+            job_algorithms: Vec::new(),
             algorithms,
             fallback,
-            total_count: Mutex::new(0),
-            fallback_count: Mutex::new(0),
         }
     }
+
+    /// Sets the job algorithms which are consulted before the rest of the chain and precede it in the member order
+    pub fn with_job_algorithms(
+        mut self,
+        algorithms: Vec<(String, Box<dyn JobCleanupAlgorithm>)>,
+    ) -> Self {
+        self.job_algorithms = algorithms;
+        self
+    }
+
+    /// Selects a pipeline and returns it alongside the index of the chain member that selected it.
+    /// The fallback algorithm is always the last member, job algorithms are not consulted.
+    pub async fn select_attributed_pipeline<'a>(
+        &self,
+        data_source: &CleanupDataSource<'a>,
+    ) -> (PipelineID, usize) {
+        for (i, (_, algorithm)) in self.algorithms.iter().enumerate() {
+            if let Some(selected_pipeline) = algorithm.select_pipeline(data_source).await {
+                return (selected_pipeline, i);
+            }
+        }
+
+        (
+            self.fallback.1.select_pipeline(data_source).await,
+            self.algorithms.len(),
+        )
+    }
 }
 
 #[async_trait]
 impl CleanupAlgorithm for FallbackCleanupAlgorithm {
     async fn select_pipeline<'a>(&self, data_source: &CleanupDataSource<'a>) -> PipelineID {
-        if std::env::var("FALLBACK_DEBUG").is_ok() {
-            println!("{:?} => {} / {}", self.algorithms, self.fallback_count.lock().await, self.total_count.lock().await);
-        }
-
-        *self.total_count.lock().await += 1;
+        self.select_attributed_pipeline(data_source).await.0
+    }
 
-        // TODO Track which algorithm answered how many requests
-        for algorithm in self.algorithms.iter() {
-            if let Some(selected_pipeline) = algorithm.select_pipeline(data_source).await {
-                return selected_pipeline;
+    /// Job algorithms are consulted first and precede the rest of the chain in the member order
+    async fn select_eviction<'a>(&self, data_source: &CleanupDataSource<'a>) -> (Eviction, usize) {
+        for (i, (_, algorithm)) in self.job_algorithms.iter().enumerate() {
+            if let Some((pipeline, job)) = algorithm.select_job(data_source).await {
+                return (Eviction::Job(pipeline, job), i);
             }
         }
 
-        *self.fallback_count.lock().await += 1;
-        self.fallback.select_pipeline(data_source).await
+        let (pipeline, member) = self.select_attributed_pipeline(data_source).await;
+
+        (
+            Eviction::Pipeline(pipeline),
+            member + self.job_algorithms.len(),
+        )
+    }
+
+    fn member_names(&self) -> Vec<String> {
+        self.job_algorithms
+            .iter()
+            .map(|(name, _)| name)
+            .chain(self.algorithms.iter().map(|(name, _)| name))
+            .chain(std::iter::once(&self.fallback.0))
+            .cloned()
+            .collect()
+    }
+
+    fn evicts_jobs(&self) -> bool {
+        !self.job_algorithms.is_empty()
+    }
+
+    async fn observe<'a>(&self, change: StateChange<'a>, data_source: &DataSource) {
+        self.fallback.1.observe(change, data_source).await;
     }
 }
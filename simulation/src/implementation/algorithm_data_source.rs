@@ -1,11 +1,11 @@
 use super::{
     data_source::{DataSource, PipelineStatus},
-    state::SimulationState,
+    state::{ScoringClass, SimulationState, StoredJob},
     PipelineID,
 };
 use anyhow::Result;
 use bytesize::ByteSize;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 
 pub struct CleanupDataSource<'a> {
     state: &'a SimulationState,
@@ -19,10 +19,89 @@ impl<'a> CleanupDataSource<'a> {
 
     /// List of stored pipeline IDs ordered by insertion time.
     /// Last item in slice equals latest insertion.
+    ///
+    /// Pipelines protected by a protection rule are omitted from this and all other pipeline
+    /// collections unless stated otherwise, so algorithms never select them.
     pub fn pipeline_ids(&self) -> &BTreeSet<PipelineID> {
-        &self.state.stored_pipelines
+        &self.state.evictable_pipelines
     }
 
+    /// Stored pipelines which have been accessed at least once, ordered by their latest access.
+    /// Ties are ordered by pipeline ID.
+    pub fn pipelines_by_last_access(&self) -> &BTreeSet<(i64, PipelineID)> {
+        &self.state.last_access_index
+    }
+
+    /// Oldest stored pipeline which has not been accessed yet.
+    /// Together with `pipelines_by_last_access` this partitions the stored pipelines.
+    pub fn oldest_unaccessed_pipeline(&self) -> Option<PipelineID> {
+        self.unaccessed_pipelines().iter().next().copied()
+    }
+
+    /// Stored pipelines which have not been accessed yet, ordered like `pipeline_ids`
+    pub fn unaccessed_pipelines(&self) -> &BTreeSet<PipelineID> {
+        &self.state.unaccessed_pipelines
+    }
+
+    /// Stored pipelines ordered by the size of their artifacts which have not been evicted yet, ties are ordered by pipeline ID.
+    pub fn pipelines_by_size(&self) -> &BTreeSet<(ByteSize, PipelineID)> {
+        &self.state.size_index
+    }
+
+    /// Stored jobs of evictable pipelines ordered by their size alongside their pipeline and their index within the
+    /// jobs of the pipeline. Ties are ordered by pipeline ID and index. Empty unless jobs are evicted individually.
+    pub fn jobs_by_size(&self) -> &BTreeSet<(ByteSize, PipelineID, usize)> {
+        &self.state.job_size_index
+    }
+
+    /// Jobs of a stored pipeline in the order of `Pipeline.jobs`, including evicted ones.
+    /// `None` if the pipeline is not stored or jobs are not evicted individually.
+    pub fn pipeline_jobs(&self, id: PipelineID) -> Option<&[StoredJob]> {
+        self.state.jobs.get(&id).map(|jobs| jobs.as_slice())
+    }
+
+    /// Stored pipelines grouped by their status and whether they have been merged.
+    /// Pipelines with an unknown status are omitted.
+    pub fn pipelines_by_scoring_class(&self) -> &HashMap<ScoringClass, BTreeSet<PipelineID>> {
+        &self.state.scoring_classes
+    }
+
+    /// Stored pipelines grouped by their status ordered by their storage time, ties are ordered by pipeline ID.
+    /// Pipelines with an unknown status are grouped under `None`.
+    pub fn pipelines_by_storage_time(
+        &self,
+    ) -> &HashMap<Option<PipelineStatus>, BTreeSet<(i64, PipelineID)>> {
+        &self.state.storage_time_index
+    }
+
+    /// Ref of a stored pipeline (including protected ones), `None` if the pipeline is not stored or its ref is unknown
+    pub fn pipeline_ref(&self, id: PipelineID) -> Option<&str> {
+        self.state.refs.get(&id).map(|r| r.as_str())
+    }
+
+    /// Category of the ref of a stored pipeline, `None` if the ref is unknown or does not belong to any category
+    pub fn ref_category(&self, id: PipelineID) -> Option<&str> {
+        self.pipeline_ref(id)
+            .and_then(|r| self.data_source.ref_classifier().category_name(r))
+    }
+
+    /// Stored pipelines of the ref ordered by their storage time, including protected ones
+    pub fn pipelines_of_ref(&self, pipeline_ref: &str) -> Option<&BTreeSet<(i64, PipelineID)>> {
+        self.state.ref_index.get(pipeline_ref)
+    }
+
+    /// Whether a newer pipeline of the same ref has been stored since, even if that one has been removed again.
+    /// Pipelines with an unknown ref are never superseded.
+    pub fn is_superseded(&self, id: PipelineID) -> bool {
+        self.state.is_superseded(id)
+    }
+
+    /// Superseded pipelines ordered by their storage time, ties are ordered by pipeline ID
+    pub fn superseded_pipelines(&self) -> &BTreeSet<(i64, PipelineID)> {
+        &self.state.superseded_index
+    }
+
+    #[allow(dead_code)]
     pub async fn pipeline_size(&self, id: PipelineID) -> Result<ByteSize> {
         Ok(self.data_source.size_of_pipeline(id).await?)
     }
@@ -32,12 +111,24 @@ impl<'a> CleanupDataSource<'a> {
     }
 
     pub fn pipeline_age(&self, id: PipelineID) -> Option<i64> {
-        let current_time = self.state.latest_event.map(|e| e.timestamp).unwrap_or(0);
+        let current_time = self.current_time();
         let storage_time = self.state.storage_times.get(&id);
 
         storage_time.map(|t| current_time - t)
     }
 
+    /// Timestamp of the next access to the pipeline after the current point in time.
+    /// Note that this peeks into the future and is thus only meant for oracle algorithms.
+    pub async fn next_access(&self, id: PipelineID) -> Result<Option<i64>> {
+        let accesses = self
+            .data_source
+            .accesses_after_timestamp(id, self.current_time())
+            .await?;
+
+        // Accesses are ordered from latest to earliest
+        Ok(accesses.last().copied())
+    }
+
     pub fn accesses(&self, id: &PipelineID) -> Option<&Vec<i64>> {
         self.state.accesses.get(id)
     }
@@ -45,4 +136,14 @@ impl<'a> CleanupDataSource<'a> {
     pub fn merges(&self) -> &BTreeSet<PipelineID> {
         &self.state.merges
     }
+
+    /// Merged pipelines ordered by the time their merge request has been merged
+    pub fn pipelines_by_merge_time(&self) -> &BTreeSet<(i64, PipelineID)> {
+        &self.state.merge_time_index
+    }
+
+    /// Timestamp of the latest event processed by the simulation
+    pub fn current_time(&self) -> i64 {
+        self.state.latest_event.map(|e| e.timestamp).unwrap_or(0)
+    }
 }
@@ -0,0 +1,252 @@
+use anyhow::{bail, Result};
+use futures::TryStreamExt;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{Connection, SqliteConnection};
+use std::{collections::HashMap, path::Path};
+
+use super::{data_source::DataSource, jobs::JobDirectory, schema::create_database};
+
+/// Refs which are kept as-is as they do not reveal anything about the project
+const PUBLIC_REFS: [&str; 2] = ["master", "premaster"];
+
+/// Ref prefixes which are kept so refs can still be categorized, only the remainder is hashed
+const PUBLIC_REF_PREFIXES: [&str; 3] = ["gitlabCI/mergeRelease/", "release/", "gitlabCI/TPH-"];
+
+/// Consistently assigns numbered pseudonyms to names in the order they are first encountered
+struct Pseudonyms {
+    prefix: &'static str,
+    names: HashMap<String, String>,
+}
+
+impl Pseudonyms {
+    fn new(prefix: &'static str) -> Self {
+        Self {
+            prefix,
+            names: HashMap::new(),
+        }
+    }
+
+    fn get(&mut self, name: &str) -> String {
+        let next = format!("{}{}", self.prefix, self.names.len());
+        self.names.entry(name.to_owned()).or_insert(next).clone()
+    }
+}
+
+/// Writes a copy of a simulation database with identifying information removed:
+/// - refs, branches and repositories are replaced by keyed hashes (HMAC-SHA256 with the salt as the key)
+/// - environments and test suites are replaced by pseudonyms
+/// - access paths, user agents, referees and file names are dropped
+/// - all timestamps are shifted by a constant offset
+pub struct Anonymizer {
+    source: DataSource,
+    salt: String,
+    time_shift: i64,
+    environments: Pseudonyms,
+    test_suites: Pseudonyms,
+}
+
+impl Anonymizer {
+    pub fn new(source: DataSource, salt: String, time_shift: i64) -> Self {
+        Self {
+            source,
+            salt,
+            time_shift,
+            environments: Pseudonyms::new("environment"),
+            test_suites: Pseudonyms::new("suite"),
+        }
+    }
+
+    pub async fn export(mut self, path: &Path) -> Result<()> {
+        if path.exists() {
+            bail!("Refusing to overwrite existing database {}", path.display());
+        }
+
+        let mut con = create_database(path).await?;
+        let mut tx = con.begin().await?;
+
+        self.export_job_size_samples(&mut tx).await?;
+        self.export_pipelines(&mut tx).await?;
+        self.export_merge_request_events(&mut tx).await?;
+        self.export_access_log(&mut tx).await?;
+        self.export_simulation_events(&mut tx).await?;
+
+        tx.commit().await?;
+        con.close().await?;
+
+        Ok(())
+    }
+
+    /// Keyed hash of the value, rendered as hex and truncated to 64 bits.
+    /// Without the salt, names can not be recovered by hashing guesses, even if some plaintexts are known.
+    fn hash(&self, value: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.salt.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(value.as_bytes());
+
+        mac.finalize().into_bytes()[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn anonymize_ref(&self, pipeline_ref: &str) -> String {
+        if PUBLIC_REFS.contains(&pipeline_ref) {
+            return pipeline_ref.to_owned();
+        }
+
+        for prefix in PUBLIC_REF_PREFIXES.iter() {
+            if let Some(remainder) = pipeline_ref.strip_prefix(prefix) {
+                return format!("{}{}", prefix, self.hash(remainder));
+            }
+        }
+
+        self.hash(pipeline_ref)
+    }
+
+    /// Shifts the timestamp while keeping unset (zero) timestamps intact
+    fn shift(&self, timestamp: i64) -> i64 {
+        if timestamp > 0 {
+            timestamp - self.time_shift
+        } else {
+            timestamp
+        }
+    }
+
+    /// Maps a job name in the format `environment:testSuite` where the environment may carry a `_reorg` suffix
+    fn anonymize_job(&mut self, job: &str) -> Option<String> {
+        let (environment, test_suite) = job.split_once(':')?;
+        let (environment, reorg) = match environment.strip_suffix("_reorg") {
+            Some(environment) => (environment, "_reorg"),
+            None => (environment, ""),
+        };
+
+        Some(format!(
+            "{}{}:{}",
+            self.environments.get(environment),
+            reorg,
+            self.test_suites.get(test_suite)
+        ))
+    }
+
+    /// Maps a job directory in the format `logs_<environment>_(reorg_)?<testSuite>...-<jobID>...`
+    fn anonymize_job_directory(&mut self, directory: &str) -> Option<String> {
+        let directory = JobDirectory::parse(directory)?;
+        let reorg = if directory.is_reorg { "reorg_" } else { "" };
+
+        Some(format!(
+            "logs_{}_{}{}-{}",
+            self.environments.get(directory.environment),
+            reorg,
+            self.test_suites.get(directory.test_suite),
+            directory.job_id
+        ))
+    }
+
+    async fn export_job_size_samples(&mut self, con: &mut SqliteConnection) -> Result<()> {
+        let samples: Vec<_> = self.source.job_size_sample_records().try_collect().await?;
+
+        for sample in samples {
+            sqlx::query("INSERT INTO JobSizeSample (id, environment, testSuite, bytes) VALUES ($1, $2, $3, $4)")
+                .bind(sample.id)
+                .bind(self.environments.get(&sample.environment))
+                .bind(self.test_suites.get(&sample.test_suite))
+                .bind(sample.bytes)
+                .execute(&mut *con)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn export_pipelines(&mut self, con: &mut SqliteConnection) -> Result<()> {
+        let pipelines: Vec<_> = self.source.pipeline_records().try_collect().await?;
+
+        for pipeline in pipelines {
+            let jobs = pipeline.jobs.map(|jobs| {
+                jobs.split(';')
+                    .filter_map(|job| self.anonymize_job(job))
+                    .collect::<Vec<_>>()
+                    .join(";")
+            });
+
+            sqlx::query("INSERT INTO Pipeline (id, status, duration, createdAt, finishedAt, ref, jobs) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+                .bind(pipeline.id)
+                .bind(pipeline.status)
+                .bind(pipeline.duration)
+                .bind(pipeline.created_at.map(|t| self.shift(t)))
+                .bind(pipeline.finished_at.map(|t| self.shift(t)))
+                .bind(pipeline.pipeline_ref.map(|r| self.anonymize_ref(&r)))
+                .bind(jobs)
+                .execute(&mut *con)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn export_merge_request_events(&mut self, con: &mut SqliteConnection) -> Result<()> {
+        let events: Vec<_> = self
+            .source
+            .merge_request_event_records()
+            .try_collect()
+            .await?;
+
+        for event in events {
+            sqlx::query("INSERT INTO MergeRequestEvent (eventID, mergeRequestID, status, sourceBranch, targetBranch, action, timestamp) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+                .bind(event.id)
+                .bind(event.merge_request)
+                .bind(event.status)
+                .bind(self.anonymize_ref(&event.source_branch))
+                .bind(self.anonymize_ref(&event.target_branch))
+                .bind(event.action)
+                .bind(event.timestamp.map(|t| self.shift(t)))
+                .execute(&mut *con)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn export_access_log(&mut self, con: &mut SqliteConnection) -> Result<()> {
+        let entries: Vec<_> = self.source.access_log_records().try_collect().await?;
+
+        for entry in entries {
+            let job = entry.job.and_then(|j| self.anonymize_job_directory(&j));
+
+            sqlx::query("INSERT INTO AccessLog (id, timestamp, method, path, status, bytes, referee, userAgent, isAutomatic, isIrrelevant, repository, pipeline, job, file) VALUES ($1, $2, $3, '', $4, $5, '-', '', $6, $7, $8, $9, $10, NULL)")
+                .bind(entry.id)
+                .bind(self.shift(entry.timestamp))
+                .bind(entry.method)
+                .bind(entry.status)
+                .bind(entry.bytes)
+                .bind(entry.is_automatic)
+                .bind(entry.is_irrelevant)
+                .bind(entry.repository.map(|r| self.hash(&r)))
+                .bind(entry.pipeline)
+                .bind(job)
+                .execute(&mut *con)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn export_simulation_events(&mut self, con: &mut SqliteConnection) -> Result<()> {
+        let events: Vec<_> = self.source.events().try_collect().await?;
+
+        for event in events {
+            sqlx::query(
+                "INSERT INTO SimulationEvent (id, timestamp, kind, key) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(event.id)
+            .bind(self.shift(event.timestamp))
+            .bind(event.kind)
+            .bind(event.key)
+            .execute(&mut *con)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::{anyhow, bail, Error, Result};
+use bytesize::ByteSize;
+use std::{fmt, str::FromStr};
+
+/// Determines when the cleanup algorithm is invoked and how much storage it frees
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CleanupMode {
+    /// Evicts pipelines as soon as the storage limit is exceeded until it is met again
+    #[default]
+    Immediate,
+    /// Starts evicting once the occupied storage exceeds the `high` fraction of the storage limit
+    /// and continues until it drops to the `low` fraction
+    Watermark { high: f64, low: f64 },
+    /// Evicts every `interval` seconds of simulated time until the occupied storage drops to the `low` fraction
+    /// of the storage limit. Storage may exceed the limit in between cleanup runs.
+    Periodic { interval: i64, low: f64 },
+}
+
+impl CleanupMode {
+    /// Occupied storage above which a cleanup run starts, `None` if runs are scheduled by time
+    pub fn trigger(&self, storage_limit: ByteSize) -> Option<ByteSize> {
+        match *self {
+            Self::Immediate => Some(storage_limit),
+            Self::Watermark { high, .. } => Some(fraction_of(storage_limit, high)),
+            Self::Periodic { .. } => None,
+        }
+    }
+
+    /// Occupied storage a cleanup run evicts down to
+    pub fn target(&self, storage_limit: ByteSize) -> ByteSize {
+        match *self {
+            Self::Immediate => storage_limit,
+            Self::Watermark { low, .. } | Self::Periodic { low, .. } => {
+                fraction_of(storage_limit, low)
+            }
+        }
+    }
+}
+
+fn fraction_of(size: ByteSize, fraction: f64) -> ByteSize {
+    ByteSize::b((size.as_u64() as f64 * fraction) as u64)
+}
+
+fn parse_fraction(fraction: &str) -> Result<f64> {
+    let value: f64 = fraction
+        .parse()
+        .map_err(|_| anyhow!("Invalid watermark '{}'", fraction))?;
+
+    if value <= 0.0 {
+        bail!("Watermarks have to be positive, got {}", value);
+    }
+
+    Ok(value)
+}
+
+impl FromStr for CleanupMode {
+    type Err = Error;
+
+    /// Parses `immediate`, `watermark:<high>:<low>` (e.g. `watermark:0.95:0.8`)
+    /// or `periodic:<hours>[:<low>]` (e.g. `periodic:24:0.8`)
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(':');
+
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("immediate"), None, None, None) => Ok(Self::Immediate),
+            (Some("watermark"), Some(high), Some(low), None) => {
+                let (high, low) = (parse_fraction(high)?, parse_fraction(low)?);
+
+                if low > high {
+                    bail!("The low watermark must not exceed the high watermark");
+                }
+
+                Ok(Self::Watermark { high, low })
+            }
+            (Some("periodic"), Some(hours), low, None) => {
+                let hours: i64 = hours
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid cleanup interval '{}'", hours))?;
+
+                if hours <= 0 {
+                    bail!("The cleanup interval has to be at least one hour");
+                }
+
+                Ok(Self::Periodic {
+                    interval: hours * 60 * 60,
+                    low: low.map_or(Ok(1.0), parse_fraction)?,
+                })
+            }
+            _ => Err(anyhow!(
+                "Unknown cleanup mode '{}' (expected immediate, watermark:<high>:<low> or periodic:<hours>[:<low>])",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CleanupMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Immediate => write!(f, "immediate"),
+            Self::Watermark { high, low } => write!(f, "watermark:{}:{}", high, low),
+            Self::Periodic { interval, low } => {
+                write!(f, "periodic:{}:{}", interval / (60 * 60), low)
+            }
+        }
+    }
+}
@@ -1,13 +1,29 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Error, Result};
 use async_std::sync::Mutex;
 use bytesize::ByteSize;
 use futures::{stream::BoxStream, TryStreamExt};
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
-use std::{collections::HashMap, convert::TryInto, sync::Arc};
-
-use super::{size_sampler::JobSizeSampler, AccessLogEntryID, MergeRequestEventID, PipelineID};
-
-#[derive(Debug, sqlx::Type, Clone, Copy, PartialEq, Eq)]
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    fmt,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+
+use super::{
+    ref_classifier::RefClassifier,
+    size_cache::SizeTable,
+    size_sampler::SizeStrategy,
+    trace_source::{
+        AccessLogRecord, JobSizeSampleRecord, MemoryTraceSource, MergeRequestEventRecord,
+        PipelineRecord, SqliteTraceSource, TraceSource,
+    },
+    AccessLogEntryID, MergeRequestEventID, PipelineID,
+};
+
+/// Kinds of simulation events, ordered like their numeric representation in the database
+#[derive(Debug, sqlx::Type, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(i32)]
 pub enum SimulationEventKind {
     PipelineCreated = 0,
@@ -24,13 +40,15 @@ pub struct SimulationEvent {
     pub key: i64,
 }
 
-#[derive(sqlx::FromRow)]
+#[derive(sqlx::FromRow, Clone)]
 pub struct AccessLogEntry {
     pub timestamp: i64,
     pub pipeline: PipelineID,
+    /// Directory of the job whose artifacts have been accessed, `None` if the access concerns the whole pipeline
+    pub job: Option<String>,
 }
 
-#[derive(sqlx::FromRow)]
+#[derive(sqlx::FromRow, Clone)]
 #[sqlx(rename_all = "camelCase")]
 pub struct MergeRequestEvent {
     pub source_branch: String,
@@ -38,7 +56,7 @@ pub struct MergeRequestEvent {
     pub action: String, // merge, ...
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PipelineStatus {
     Pending,
     Running,
@@ -54,24 +72,52 @@ pub enum PipelineStatus {
 
 impl PipelineStatus {
     pub fn from_string(source: &str) -> Self {
-        match source {
-            "pending" => PipelineStatus::Pending,
-            "running" => PipelineStatus::Running,
-            "success" => PipelineStatus::Success,
-            "failed" => PipelineStatus::Failed,
-            "canceled" => PipelineStatus::Cancelled,
-            "skipped" => PipelineStatus::Skipped,
-            "created" => PipelineStatus::Created,
-            "manual" => PipelineStatus::Manual,
-            e => {
-                eprintln!("Encountered unexpected pipeline status: '{}'", e);
+        match source.parse() {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("{}", e);
                 unreachable!()
             }
         }
     }
 }
 
-#[derive(sqlx::FromRow)]
+impl fmt::Display for PipelineStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PipelineStatus::Pending => "pending",
+            PipelineStatus::Running => "running",
+            PipelineStatus::Success => "success",
+            PipelineStatus::Failed => "failed",
+            PipelineStatus::Cancelled => "canceled",
+            PipelineStatus::Skipped => "skipped",
+            PipelineStatus::Created => "created",
+            PipelineStatus::Manual => "manual",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for PipelineStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pending" => Ok(PipelineStatus::Pending),
+            "running" => Ok(PipelineStatus::Running),
+            "success" => Ok(PipelineStatus::Success),
+            "failed" => Ok(PipelineStatus::Failed),
+            "canceled" => Ok(PipelineStatus::Cancelled),
+            "skipped" => Ok(PipelineStatus::Skipped),
+            "created" => Ok(PipelineStatus::Created),
+            "manual" => Ok(PipelineStatus::Manual),
+            e => Err(anyhow!("Encountered unexpected pipeline status: '{}'", e)),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow, Clone)]
 #[sqlx(rename_all = "camelCase")]
 pub struct Pipeline {
     pub id: PipelineID,
@@ -89,34 +135,94 @@ pub struct Pipeline {
 //     }
 // }
 
+/// Trace files with one of these extensions are replayed from memory instead of being read from a SQLite database
+const TRACE_FILE_EXTENSIONS: [&str; 2] = ["jsonl", "csv"];
+
+fn is_trace_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| TRACE_FILE_EXTENSIONS.contains(&e))
+}
+
+/// Names and sizes of the jobs of each pipeline, `None` if the size of any job could not be sampled
+type JobTable = HashMap<PipelineID, Option<Arc<Vec<(String, ByteSize)>>>>;
+
+/// Metadata of all pipelines created by events, looked up once before simulating and read without locking afterwards.
+/// Lookups of other pipelines fall back to the caches of the data source.
+#[derive(Default)]
+struct FrozenTables {
+    sizes: SizeTable,
+    statuses: HashMap<PipelineID, PipelineStatus>,
+    refs: HashMap<PipelineID, Option<String>>,
+    jobs: JobTable,
+}
+
 #[derive(Clone)]
 pub struct DataSource {
-    con: SqlitePool,
-    sampler: Arc<Mutex<JobSizeSampler>>,
+    source: Arc<dyn TraceSource>,
 
-    sizes: Arc<Mutex<HashMap<PipelineID, i64>>>,
+    sizes: Arc<Mutex<SizeTable>>,
 
     status_cache: Arc<Mutex<HashMap<PipelineID, PipelineStatus>>>,
+
+    ref_cache: Arc<Mutex<HashMap<PipelineID, Option<String>>>>,
+
+    job_cache: Arc<Mutex<JobTable>>,
+
+    frozen: Option<Arc<FrozenTables>>,
+
+    ref_classifier: Arc<RefClassifier>,
 }
 
 impl DataSource {
-    pub async fn open(database: &str, seed: u64) -> Result<Self> {
-        // Note: When running in parallel the JobSizeSampler might not be called in the same order due to thread timing
-        //       This might yield indeterministic behavior!
-        // TODO: Circumvent this by pre-populating the sampler.
-        let con = SqlitePoolOptions::new()
-            .min_connections(64)
-            .max_connections(100)
-            .connect(database)
-            .await?;
+    /// Opens either a SQLite database or a JSONL/CSV trace file depending on the file extension.
+    /// The seed and size strategy are only used to sample the pipeline sizes of databases.
+    pub async fn open(database: &str, seed: u64, size_strategy: SizeStrategy) -> Result<Self> {
+        if is_trace_file(database) {
+            Ok(Self::new(Arc::new(MemoryTraceSource::load(Path::new(
+                database,
+            ))?)))
+        } else {
+            Ok(Self::new(Arc::new(
+                SqliteTraceSource::open(database, seed, size_strategy).await?,
+            )))
+        }
+    }
+
+    /// Like `open` but loads all events and metadata of a database into memory up front.
+    /// This avoids querying the database for every event which is shared between all simulations.
+    pub async fn preload(database: &str, seed: u64, size_strategy: SizeStrategy) -> Result<Self> {
+        if is_trace_file(database) {
+            return Self::open(database, seed, size_strategy).await;
+        }
+
+        let source = SqliteTraceSource::open(database, seed, size_strategy).await?;
+
+        Ok(Self::new(Arc::new(
+            MemoryTraceSource::preload(source).await?,
+        )))
+    }
 
-        Ok(Self {
-            // con: SqlitePool::connect(database).await?,
-            con,
-            sampler: Arc::new(Mutex::new(JobSizeSampler::new(seed))),
+    pub fn new(source: Arc<dyn TraceSource>) -> Self {
+        Self {
+            source,
             sizes: Arc::new(Mutex::new(HashMap::new())),
             status_cache: Arc::new(Mutex::new(HashMap::new())),
-        })
+            ref_cache: Arc::new(Mutex::new(HashMap::new())),
+            job_cache: Arc::new(Mutex::new(HashMap::new())),
+            frozen: None,
+            ref_classifier: Arc::new(RefClassifier::default()),
+        }
+    }
+
+    /// Replaces the default categories used to classify refs
+    pub fn set_ref_classifier(&mut self, classifier: RefClassifier) {
+        self.ref_classifier = Arc::new(classifier);
+    }
+
+    pub fn ref_classifier(&self) -> &RefClassifier {
+        &self.ref_classifier
     }
 
     pub async fn populate_size_samples(&self) -> Result<ByteSize> {
@@ -140,128 +246,190 @@ impl DataSource {
         Ok(total_size)
     }
 
-    pub fn events(&self) -> BoxStream<Result<SimulationEvent, sqlx::Error>> {
-        sqlx::query_as("SELECT * FROM SimulationEvent ORDER BY timestamp").fetch(&self.con)
+    /// Looks up the metadata of all pipelines created by events, jobs only if requested as sampling them is expensive.
+    /// Subsequent lookups of these pipelines, including those of clones of this data source, no longer lock the caches.
+    pub async fn freeze(&mut self, include_jobs: bool) -> Result<()> {
+        let pipelines = self
+            .events()
+            .try_filter(|event| {
+                futures::future::ready(matches!(
+                    event.kind,
+                    SimulationEventKind::PipelineCreated | SimulationEventKind::PipelineFinished
+                ))
+            })
+            .map_ok(|event| event.key)
+            .try_collect::<HashSet<_>>()
+            .await?;
+
+        for id in pipelines {
+            // Failed lookups are not cached and thus retried when the pipeline is looked up again
+            let _ = self.size_of_pipeline(id).await;
+            let _ = self.status_of_pipeline(id).await;
+            let _ = self.ref_of_pipeline(id).await;
+            if include_jobs {
+                let _ = self.jobs_of_pipeline(id).await;
+            }
+        }
+
+        self.frozen = Some(Arc::new(FrozenTables {
+            sizes: self.sizes.lock().await.clone(),
+            statuses: self.status_cache.lock().await.clone(),
+            refs: self.ref_cache.lock().await.clone(),
+            jobs: self.job_cache.lock().await.clone(),
+        }));
+
+        Ok(())
+    }
+
+    /// Sizes of all pipelines sampled so far
+    pub async fn size_table(&self) -> SizeTable {
+        self.sizes.lock().await.clone()
+    }
+
+    /// Uses previously sampled sizes instead of sampling them again
+    pub async fn import_size_table(&self, table: SizeTable) {
+        self.sizes.lock().await.extend(table);
+    }
+
+    pub fn events(&self) -> BoxStream<Result<SimulationEvent>> {
+        self.source.events()
     }
 
     pub async fn event_count(&self) -> Result<u64> {
-        let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM SimulationEvent")
-            .fetch_one(&self.con)
-            .await?;
-        Ok(row.0.try_into()?)
+        self.source.event_count().await
     }
 
     pub async fn access_log_entry(&self, id: AccessLogEntryID) -> Result<AccessLogEntry> {
-        Ok(
-            sqlx::query_as("SELECT timestamp,pipeline FROM AccessLog WHERE id=$1")
-                .bind(id)
-                .fetch_one(&self.con)
-                .await?,
-        )
+        self.source.access_log_entry(id).await
     }
 
     pub async fn merge_request_event(&self, id: MergeRequestEventID) -> Result<MergeRequestEvent> {
-        Ok(sqlx::query_as(
-            "SELECT sourceBranch,status,action FROM MergeRequestEvent WHERE eventID=$1",
-        )
-        .bind(id)
-        .fetch_one(&self.con)
-        .await?)
-    }
-
-    pub async fn pipeline(&self, id: PipelineID) -> Result<Pipeline> {
-        Ok(
-            sqlx::query_as("SELECT id,jobs,status,duration,createdAt FROM Pipeline WHERE id=$1")
-                .bind(id)
-                .fetch_one(&self.con)
-                .await?,
-        )
+        self.source.merge_request_event(id).await
     }
 
     pub async fn pipelines_for_ref(&self, pipeline_ref: String) -> Result<Vec<Pipeline>> {
-        Ok(
-            sqlx::query_as("SELECT id,jobs,status,duration,createdAt FROM Pipeline WHERE ref=$1")
-                .bind(pipeline_ref)
-                .fetch_all(&self.con)
-                .await?,
-        )
+        self.source.pipelines_for_ref(pipeline_ref).await
     }
 
     /// Evaluates whether a pipeline has metadata from Gitlab or is derived from an AccessLogEntry (the latter happens especially in the beginning where not all pipelines are available)
     /// Also checks if a pipeline has size data available
     pub async fn pipeline_is_populated(&self, id: PipelineID) -> Result<bool> {
-        let row: (Option<i64>,) = sqlx::query_as("SELECT createdAt FROM Pipeline WHERE id=$1")
-            .bind(id)
-            .fetch_one(&self.con)
-            .await?;
-
-        let has_gitlab_data = row.0 != None;
+        let has_gitlab_data = self.source.has_pipeline_metadata(id).await?;
         let has_size = self.size_of_pipeline(id).await.is_ok();
 
         Ok(has_gitlab_data && has_size)
     }
 
     pub async fn size_of_pipeline(&self, id: PipelineID) -> Result<ByteSize> {
-        let mut sizes = self.sizes.lock().await;
-
-        if let Some(size) = sizes.get(&id) {
-            return Ok(ByteSize::b((*size).try_into().unwrap()));
-        }
-
-        let mut total_size = 0;
-        let mut sampler = self.sampler.lock().await;
-
-        let mut missed_count = 0;
-        for job in self.pipeline(id).await?.jobs.split(";") {
-            match sampler.sample(job, &self.con).await {
-                Ok(size) => total_size += size,
-                Err(_e) => {
-                    missed_count += 1;
-                    // eprintln!("Ignoring job {} of pipeline {}: {:?}", job, id, e)
+        let size = match self.frozen.as_ref().and_then(|f| f.sizes.get(&id)) {
+            Some(size) => *size,
+            None => {
+                let mut sizes = self.sizes.lock().await;
+
+                match sizes.get(&id) {
+                    Some(size) => *size,
+                    None => {
+                        let size = self.source.pipeline_size(id).await?;
+                        sizes.insert(id, size);
+                        size
+                    }
                 }
             }
-        }
+        };
 
-        if missed_count > 0 {
-            bail!("Not enough size samples available for pipeline {}!", id);
+        match size {
+            Some(size) => Ok(ByteSize::b(size.try_into().unwrap())),
+            None => bail!("Not enough size samples available for pipeline {}!", id),
         }
-
-        sizes.insert(id, total_size);
-
-        Ok(ByteSize::b(total_size.try_into().unwrap()))
     }
 
     pub async fn status_of_pipeline(&self, id: PipelineID) -> Result<PipelineStatus> {
+        if let Some(status) = self.frozen.as_ref().and_then(|f| f.statuses.get(&id)) {
+            return Ok(*status);
+        }
+
         if let Some(cache_value) = self.status_cache.lock().await.get(&id) {
             return Ok(*cache_value);
         }
 
-        let row: (String,) = sqlx::query_as("SELECT status FROM Pipeline WHERE id=$1")
-            .bind(id)
-            .fetch_one(&self.con)
-            .await?;
-
-        let status = PipelineStatus::from_string(&row.0);
+        let status = self.source.pipeline_status(id).await?;
 
         self.status_cache.lock().await.insert(id, status);
 
         Ok(status)
     }
 
+    /// Ref the pipeline ran on, `None` if the pipeline or its ref is unknown
+    pub async fn ref_of_pipeline(&self, id: PipelineID) -> Result<Option<String>> {
+        if let Some(pipeline_ref) = self.frozen.as_ref().and_then(|f| f.refs.get(&id)) {
+            return Ok(pipeline_ref.clone());
+        }
+
+        if let Some(cache_value) = self.ref_cache.lock().await.get(&id) {
+            return Ok(cache_value.clone());
+        }
+
+        let pipeline_ref = self.source.pipeline_ref(id).await?;
+
+        self.ref_cache.lock().await.insert(id, pipeline_ref.clone());
+
+        Ok(pipeline_ref)
+    }
+
+    /// Names and sizes of the jobs of the pipeline, their sizes add up to the size of the pipeline
+    pub async fn jobs_of_pipeline(&self, id: PipelineID) -> Result<Arc<Vec<(String, ByteSize)>>> {
+        let cache_value = match self.frozen.as_ref().and_then(|f| f.jobs.get(&id)) {
+            Some(cache_value) => cache_value.clone(),
+            None => {
+                let mut jobs = self.job_cache.lock().await;
+
+                match jobs.get(&id) {
+                    Some(cache_value) => cache_value.clone(),
+                    None => {
+                        let cache_value = self.source.job_sizes(id).await?.map(|sizes| {
+                            Arc::new(
+                                sizes
+                                    .into_iter()
+                                    .map(|(job, size)| (job, ByteSize::b(size.try_into().unwrap())))
+                                    .collect(),
+                            )
+                        });
+                        jobs.insert(id, cache_value.clone());
+                        cache_value
+                    }
+                }
+            }
+        };
+
+        cache_value.ok_or_else(|| {
+            anyhow!(
+                "Not enough size samples available for the jobs of pipeline {}!",
+                id
+            )
+        })
+    }
+
+    /// Repository through which the pipeline has first been accessed, `None` if it is unknown
+    pub async fn repository_of_pipeline(&self, id: PipelineID) -> Result<Option<String>> {
+        self.source.pipeline_repository(id).await
+    }
+
+    /// Duration it took the pipeline to run in seconds, pipelines without a known duration are treated as instant
+    pub async fn duration_of_pipeline(&self, id: PipelineID) -> Result<i64> {
+        self.source.pipeline_duration(id).await
+    }
+
     #[allow(dead_code)]
     pub async fn will_pipeline_be_accessed_after_timestamp(
         &self,
         id: PipelineID,
         timestamp: i64,
     ) -> Result<bool> {
-        let row: (i64,) =
-            sqlx::query_as("SELECT COUNT(*) FROM AccessLog WHERE pipeline=$1 AND timestamp>$2 AND NOT isIrrelevant AND NOT isAutomatic")
-                .bind(id)
-                .bind(timestamp)
-                .fetch_one(&self.con)
-                .await?;
-
-        Ok(row.0 > 0)
+        Ok(!self
+            .source
+            .accesses_after_timestamp(id, timestamp)
+            .await?
+            .is_empty())
     }
 
     pub async fn accesses_after_timestamp(
@@ -269,16 +437,26 @@ impl DataSource {
         id: PipelineID,
         timestamp: i64,
     ) -> Result<Vec<i64>> {
-        let rows: Vec<(i64,)> = sqlx::query_as("SELECT timestamp FROM AccessLog WHERE pipeline=$1 AND timestamp>$2 AND NOT isIrrelevant AND NOT isAutomatic ORDER BY timestamp DESC")
-            .bind(id)
-            .bind(timestamp)
-            .fetch_all(&self.con)
-            .await?;
+        self.source.accesses_after_timestamp(id, timestamp).await
+    }
+
+    pub fn all_pipelines(&self) -> BoxStream<Result<Pipeline>> {
+        self.source.all_pipelines()
+    }
+
+    pub fn job_size_sample_records(&self) -> BoxStream<'_, Result<JobSizeSampleRecord>> {
+        self.source.job_size_sample_records()
+    }
+
+    pub fn pipeline_records(&self) -> BoxStream<'_, Result<PipelineRecord>> {
+        self.source.pipeline_records()
+    }
 
-        Ok(rows.into_iter().map(|r| r.0).collect())
+    pub fn merge_request_event_records(&self) -> BoxStream<'_, Result<MergeRequestEventRecord>> {
+        self.source.merge_request_event_records()
     }
 
-    pub fn all_pipelines(&self) -> BoxStream<Result<Pipeline, sqlx::Error>> {
-        sqlx::query_as("SELECT * FROM Pipeline WHERE createdAt > 0").fetch(&self.con)
+    pub fn access_log_records(&self) -> BoxStream<'_, Result<AccessLogRecord>> {
+        self.source.access_log_records()
     }
 }
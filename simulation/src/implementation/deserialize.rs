@@ -0,0 +1,27 @@
+use regex::Regex;
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use super::PipelineStatus;
+
+/// Deserializes an optional pipeline status given by its name (e.g. `failed`)
+pub fn deserialize_status<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<PipelineStatus>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|status| status.parse().map_err(D::Error::custom))
+        .transpose()
+}
+
+/// Deserializes a regular expression given as a string
+pub fn deserialize_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    Regex::new(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+/// Deserializes an optional regular expression given as a string
+pub fn deserialize_optional_regex<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Regex>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| Regex::new(&pattern).map_err(D::Error::custom))
+        .transpose()
+}
@@ -0,0 +1,140 @@
+use anyhow::Result;
+use bytesize::ByteSize;
+use std::{
+    collections::HashMap, fmt, fs::create_dir_all, fs::File, io::BufWriter, io::Write,
+    path::PathBuf,
+};
+
+use super::{PipelineID, PipelineStatus};
+
+/// Properties of a pipeline at the time it has been evicted
+pub struct EvictionRecord {
+    pub timestamp: i64,
+    pub pipeline: PipelineID,
+    pub size: ByteSize,
+    /// Status of the pipeline, flat traces may omit it
+    pub status: Option<PipelineStatus>,
+    pub age: i64,
+    pub access_count: usize,
+    /// Name of the algorithm chain member which selected the pipeline
+    pub algorithm: String,
+    /// Whether the pipeline has been accessed after it was evicted
+    pub regretted: bool,
+}
+
+impl EvictionRecord {
+    pub fn csv_header() -> &'static str {
+        "Timestamp,Pipeline,Size,Status,Age,Access count,Algorithm,Regretted"
+    }
+}
+
+impl fmt::Display for EvictionRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Pipelines without a known status are written with an empty cell
+        let status = self
+            .status
+            .map(|status| format!("{:?}", status))
+            .unwrap_or_default();
+
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}",
+            self.timestamp,
+            self.pipeline,
+            self.size.as_u64(),
+            status,
+            self.age,
+            self.access_count,
+            self.algorithm,
+            self.regretted as u8
+        )
+    }
+}
+
+/// Log of all evictions made during a simulation
+pub struct EvictionTrace {
+    records: Vec<EvictionRecord>,
+    /// Index of the latest record for each evicted pipeline
+    latest_records: HashMap<PipelineID, usize>,
+}
+
+impl EvictionTrace {
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+            latest_records: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, record: EvictionRecord) {
+        self.latest_records
+            .insert(record.pipeline, self.records.len());
+        self.records.push(record);
+    }
+
+    /// Marks the eviction of the pipeline as regretted, to be called when an evicted pipeline is accessed
+    pub fn mark_regretted(&mut self, id: PipelineID) {
+        if let Some(index) = self.latest_records.get(&id) {
+            self.records[*index].regretted = true;
+        }
+    }
+
+    /// Combines multiple traces into a single one ordered by the time of eviction
+    pub fn merge(traces: Vec<EvictionTrace>) -> Self {
+        let mut records = traces
+            .into_iter()
+            .flat_map(|trace| trace.records)
+            .collect::<Vec<_>>();
+        records.sort_by_key(|record| record.timestamp);
+
+        let mut trace = Self::new();
+        for record in records {
+            trace.record(record);
+        }
+
+        trace
+    }
+
+    pub fn write_csv(&self, path: PathBuf) -> Result<()> {
+        create_dir_all(path.parent().unwrap())?;
+        let mut f = BufWriter::new(File::create(path)?);
+
+        writeln!(f, "{}", EvictionRecord::csv_header())?;
+
+        for record in self.records.iter() {
+            writeln!(f, "{}", record)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytesize::ByteSize;
+
+    use super::EvictionRecord;
+    use crate::implementation::PipelineStatus;
+
+    fn record(status: Option<PipelineStatus>) -> EvictionRecord {
+        EvictionRecord {
+            timestamp: 1000,
+            pipeline: 1,
+            size: ByteSize::b(100),
+            status,
+            age: 10,
+            access_count: 2,
+            algorithm: "FIFO".to_owned(),
+            regretted: true,
+        }
+    }
+
+    #[test]
+    fn unknown_status_is_left_empty() {
+        assert_eq!(
+            record(Some(PipelineStatus::Success)).to_string(),
+            "1000,1,100,Success,10,2,FIFO,1"
+        );
+        assert_eq!(record(None).to_string(), "1000,1,100,,10,2,FIFO,1");
+    }
+}
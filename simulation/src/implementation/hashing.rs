@@ -0,0 +1,15 @@
+//! Hash functions whose results are stable across platforms and compiler versions, unlike the `DefaultHasher`
+
+/// Finalizer of the SplitMix64 generator, spreads similar inputs across the whole output range
+pub fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+    value ^ (value >> 31)
+}
+
+/// FNV-1a hash of a sequence of bytes
+pub fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
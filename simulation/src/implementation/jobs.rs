@@ -0,0 +1,51 @@
+/// Job directory an access refers to, in the format `logs_<environment>_(reorg_)?<testSuite>...-<jobID>...`
+pub struct JobDirectory<'a> {
+    pub environment: &'a str,
+    pub is_reorg: bool,
+    pub test_suite: &'a str,
+    pub job_id: &'a str,
+}
+
+impl<'a> JobDirectory<'a> {
+    pub fn parse(directory: &'a str) -> Option<Self> {
+        let (environment, remainder) = directory.strip_prefix("logs_")?.split_once('_')?;
+        let (remainder, is_reorg) = match remainder.strip_prefix("reorg_") {
+            Some(remainder) => (remainder, true),
+            None => (remainder, false),
+        };
+        let (test_suite, remainder) = remainder.split_at(remainder.find(['_', '-'])?);
+        let job_id = remainder
+            .split('-')
+            .nth(1)
+            .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))?;
+
+        Some(Self {
+            environment,
+            is_reorg,
+            test_suite,
+            job_id,
+        })
+    }
+
+    /// Whether the directory belongs to the job with the given name (`<environment>:<testSuite>`).
+    /// Reorg runs are treated as runs of the regular job, just like when sampling job sizes.
+    pub fn is_job(&self, job: &str) -> bool {
+        match job.split_once(':') {
+            Some((environment, test_suite)) => {
+                environment.trim_end_matches("_reorg") == self.environment
+                    && test_suite == self.test_suite
+            }
+            None => false,
+        }
+    }
+}
+
+/// Index of the job within the jobs of a pipeline the job directory of an access refers to
+pub fn job_index<'a>(
+    jobs: impl IntoIterator<Item = &'a str>,
+    directory: Option<&str>,
+) -> Option<usize> {
+    let directory = JobDirectory::parse(directory?)?;
+
+    jobs.into_iter().position(|job| directory.is_job(job))
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use super::{
     data_source::DataSource, state::SimulationState, CleanupAlgorithm, CleanupDataSource,
-    PipelineID, PipelineStatus,
+    PipelineID, PipelineStatus, RefClassifier, SizeStrategy,
 };
 use anyhow::{anyhow, Result};
 use async_std::prelude::*;
@@ -28,7 +28,8 @@ struct MLDataPoint {
     age: i64,
     access_count: usize,
     still_needed: bool,
-    // TODO Add type/some abstraction of ref (so we can catch premaster and stuff)
+    /// Category of the ref as numeric ref type, see `RefClassifier::ref_type`
+    ref_type: usize,
 }
 
 impl MLDataPoint {
@@ -76,6 +77,11 @@ impl MLDataPoint {
 
         let access_count = state.accesses.get(&pipeline_id).map_or(0, |a| a.len());
 
+        let pipeline_ref = data_source.ref_of_pipeline(pipeline_id).await?;
+        let ref_type = data_source
+            .ref_classifier()
+            .ref_type(pipeline_ref.as_deref());
+
         Ok(Self {
             status,
             size,
@@ -83,22 +89,24 @@ impl MLDataPoint {
             age,
             access_count,
             still_needed,
+            ref_type,
         })
     }
 
     fn csv_header() -> &'static str {
-        "status,size,merged,age,accessCount,stillNeeded\n"
+        "status,size,merged,age,accessCount,stillNeeded,refType\n"
     }
 
     fn serialize(&self) -> String {
         format!(
-            "{:?},{},{},{},{},{}\n",
+            "{:?},{},{},{},{},{},{}\n",
             self.status,
             self.size.as_u64(),
             self.merged as u8,
             self.age,
             self.access_count,
-            self.still_needed as u8
+            self.still_needed as u8,
+            self.ref_type
         )
     }
 }
@@ -109,8 +117,15 @@ pub struct MLGenerator {
 }
 
 impl MLGenerator {
-    pub async fn prepare(database: &str, seed: u64) -> Result<Self> {
-        let data_source = DataSource::open(database, seed).await?;
+    pub async fn prepare(
+        database: &str,
+        seed: u64,
+        size_strategy: SizeStrategy,
+        ref_classifier: RefClassifier,
+    ) -> Result<Self> {
+        let mut data_source = DataSource::open(database, seed, size_strategy).await?;
+        data_source.set_ref_classifier(ref_classifier);
+
         let event_count = data_source.event_count().await?;
         let progress_bar = ProgressBar::new(event_count);
 
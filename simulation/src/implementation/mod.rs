@@ -4,18 +4,42 @@ pub type MergeRequestEventID = i64;
 
 mod algorithm;
 mod algorithm_data_source;
+mod anonymizer;
+mod cleanup_mode;
 mod data_source;
+mod deserialize;
+mod eviction_trace;
+mod hashing;
+mod jobs;
 mod ml_generator;
+mod protection;
+mod ref_classifier;
+mod schema;
 mod simulation;
+mod size_cache;
 mod size_sampler;
 mod state;
 mod statistics;
 mod static_ml_generator;
+mod tenants;
+mod trace_source;
 
-pub use algorithm::{CleanupAlgorithm, CleanupAttemptAlgorithm, FallbackCleanupAlgorithm};
+pub use algorithm::{
+    CleanupAlgorithm, CleanupAttemptAlgorithm, CleanupRules, Eviction, FallbackCleanupAlgorithm,
+    JobCleanupAlgorithm, RetentionPolicy, StateChange,
+};
 pub use algorithm_data_source::CleanupDataSource;
-pub use data_source::{PipelineStatus, DataSource};
+pub use anonymizer::Anonymizer;
+pub use cleanup_mode::CleanupMode;
+pub use data_source::{DataSource, PipelineStatus, SimulationEvent, SimulationEventKind};
+pub use deserialize::{deserialize_regex, deserialize_status};
+pub use protection::ProtectionRule;
+pub use ref_classifier::RefClassifier;
+pub use schema::create_database;
 pub use simulation::Simulation;
-pub use statistics::{DataPoint, Statistics};
+pub use size_cache::SizeCache;
+pub use size_sampler::{SizeDistribution, SizeStrategy};
+pub use statistics::{DataPoint, Statistics, Summary};
 pub use ml_generator::MLGenerator;
-pub use static_ml_generator::StaticMLGenerator;
\ No newline at end of file
+pub use static_ml_generator::StaticMLGenerator;
+pub use tenants::TenantConfig;
\ No newline at end of file
@@ -0,0 +1,68 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+use super::{
+    data_source::PipelineStatus,
+    deserialize::{deserialize_optional_regex, deserialize_status},
+    PipelineID,
+};
+
+/// Properties of a stored pipeline the protection rules are evaluated against
+pub struct ProtectionCandidate<'a> {
+    pub id: PipelineID,
+    pub pipeline_ref: Option<&'a str>,
+    pub status: Option<PipelineStatus>,
+    /// Seconds since the pipeline has been stored
+    pub age: i64,
+    /// Number of newer stored pipelines of the same ref, `None` if the ref is unknown.
+    /// Only counted up to the largest `latest` limit of all rules, as larger ranks match no rule limited by it.
+    pub rank: Option<usize>,
+}
+
+/// Exempts pipelines from eviction by both the cleanup algorithms and the retention policies.
+/// A rule protects a pipeline if it meets all of the given criteria, omitted criteria always match.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ProtectionRule {
+    pub name: String,
+    /// Regular expression the ref of the pipeline has to match
+    #[serde(
+        rename = "ref",
+        default,
+        deserialize_with = "deserialize_optional_regex"
+    )]
+    pub ref_pattern: Option<Regex>,
+    #[serde(default, deserialize_with = "deserialize_status")]
+    pub status: Option<PipelineStatus>,
+    /// Protection ends once the pipeline has been stored for longer than this many seconds
+    pub max_age: Option<i64>,
+    /// Only the newest `latest` stored pipelines of each ref are protected
+    pub latest: Option<usize>,
+    /// Pipelines which have been pinned manually
+    pub pipelines: Option<HashSet<PipelineID>>,
+}
+
+impl ProtectionRule {
+    pub fn matches(&self, candidate: &ProtectionCandidate) -> bool {
+        let matches_ref = match (&self.ref_pattern, candidate.pipeline_ref) {
+            (Some(pattern), Some(pipeline_ref)) => pattern.is_match(pipeline_ref),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let matches_rank = match (self.latest, candidate.rank) {
+            (Some(latest), Some(rank)) => rank < latest,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        let matches_status = self.status.is_none() || self.status == candidate.status;
+        let matches_age = !matches!(self.max_age, Some(max_age) if candidate.age > max_age);
+        let matches_pipeline = !matches!(
+            &self.pipelines,
+            Some(pipelines) if !pipelines.contains(&candidate.id)
+        );
+
+        matches_ref && matches_rank && matches_status && matches_age && matches_pipeline
+    }
+}
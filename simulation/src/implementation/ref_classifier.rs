@@ -0,0 +1,90 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+use super::deserialize::deserialize_regex;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RefCategory {
+    pub name: String,
+    /// Regular expression matched against the ref of a pipeline
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub pattern: Regex,
+}
+
+/// Assigns refs to categories like master, release or merge request branches.
+/// Each ref belongs to the first category whose pattern it matches.
+///
+/// ```toml
+/// [[category]]
+/// name = "master"
+/// pattern = "^(master|main)$"
+///
+/// [[category]]
+/// name = "release"
+/// pattern = "^release/"
+/// ```
+#[derive(Deserialize, Clone, Debug)]
+pub struct RefClassifier {
+    #[serde(rename = "category", default)]
+    pub categories: Vec<RefCategory>,
+}
+
+impl RefClassifier {
+    pub fn load(path: &Path) -> Result<Self> {
+        let classifier: Self = toml::from_str(&fs::read_to_string(path)?)?;
+
+        if classifier.categories.is_empty() {
+            bail!(
+                "Ref category file {} does not define any categories",
+                path.display()
+            );
+        }
+
+        Ok(classifier)
+    }
+
+    /// Index of the first category matching the ref
+    pub fn classify(&self, pipeline_ref: &str) -> Option<usize> {
+        self.categories
+            .iter()
+            .position(|c| c.pattern.is_match(pipeline_ref))
+    }
+
+    pub fn category_name(&self, pipeline_ref: &str) -> Option<&str> {
+        self.classify(pipeline_ref)
+            .map(|i| self.categories[i].name.as_str())
+    }
+
+    /// Numeric ref type as used in `ml_data.csv`: zero for unknown refs and refs without a category,
+    /// otherwise the index of the category plus one
+    pub fn ref_type(&self, pipeline_ref: Option<&str>) -> usize {
+        pipeline_ref
+            .and_then(|r| self.classify(r))
+            .map_or(0, |i| i + 1)
+    }
+}
+
+impl Default for RefClassifier {
+    /// Categories of the original dataset in the order of the ref types in `ml_data.csv`
+    fn default() -> Self {
+        let categories = [
+            ("premaster", "^premaster$"),
+            ("master", "^master$"),
+            ("merge-release", "^gitlabCI/mergeRelease/"),
+            ("release", "^release/"),
+            ("merge-request", "^gitlabCI/TPH-"),
+        ];
+
+        Self {
+            categories: categories
+                .iter()
+                .map(|(name, pattern)| RefCategory {
+                    name: (*name).to_owned(),
+                    pattern: Regex::new(pattern).unwrap(),
+                })
+                .collect(),
+        }
+    }
+}
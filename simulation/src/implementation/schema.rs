@@ -0,0 +1,66 @@
+use anyhow::Result;
+use sqlx::{sqlite::SqliteConnectOptions, ConnectOptions, Executor, SqliteConnection};
+use std::path::Path;
+
+/// Tables read by the simulation, mirroring the schema created by the `big-data-pipeline`
+pub const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS "SimulationEvent" (
+    "id" INTEGER PRIMARY KEY NOT NULL,
+    "timestamp" INTEGER NOT NULL,
+    "kind" INTEGER NOT NULL,
+    "key" INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS "JobSizeSample" (
+    "id" INTEGER PRIMARY KEY NOT NULL,
+    "environment" TEXT NOT NULL,
+    "testSuite" TEXT NOT NULL,
+    "bytes" INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS "MergeRequestEvent" (
+    "eventID" INTEGER PRIMARY KEY NOT NULL,
+    "mergeRequestID" INTEGER NOT NULL,
+    "status" TEXT,
+    "sourceBranch" TEXT NOT NULL,
+    "targetBranch" TEXT NOT NULL,
+    "action" TEXT NOT NULL,
+    "timestamp" INTEGER
+);
+CREATE TABLE IF NOT EXISTS "Pipeline" (
+    "id" INTEGER PRIMARY KEY NOT NULL,
+    "status" TEXT,
+    "duration" INTEGER,
+    "createdAt" INTEGER,
+    "finishedAt" INTEGER,
+    "ref" TEXT,
+    "jobs" TEXT
+);
+CREATE TABLE IF NOT EXISTS "AccessLog" (
+    "id" INTEGER PRIMARY KEY NOT NULL,
+    "timestamp" INTEGER NOT NULL,
+    "method" TEXT NOT NULL,
+    "path" TEXT NOT NULL,
+    "status" INTEGER NOT NULL,
+    "bytes" INTEGER NOT NULL,
+    "referee" TEXT NOT NULL,
+    "userAgent" TEXT NOT NULL,
+    "isAutomatic" INTEGER NOT NULL,
+    "isIrrelevant" INTEGER NOT NULL,
+    "repository" TEXT,
+    "pipeline" INTEGER REFERENCES "Pipeline" ("id"),
+    "job" TEXT,
+    "file" TEXT
+);
+"#;
+
+/// Creates a new database at the given path containing all tables of the simulation schema
+pub async fn create_database(path: &Path) -> Result<SqliteConnection> {
+    let mut con = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .connect()
+        .await?;
+
+    con.execute(SCHEMA).await?;
+
+    Ok(con)
+}
@@ -1,14 +1,22 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use bytesize::ByteSize;
 use futures::TryStreamExt;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 
-use super::{data_source::DataSource, state::SimulationState, CleanupAlgorithm, Statistics};
+use std::{sync::Arc, time::Instant};
+
+use super::{
+    data_source::DataSource, state::SimulationState, tenants::MultiTenantState, CleanupAlgorithm,
+    CleanupMode, CleanupRules, Statistics, TenantConfig,
+};
 
 pub struct Simulation {
     statistics: Statistics,
     data_source: DataSource,
     progress_bar: ProgressBar,
+    trace_evictions: bool,
+    cleanup_mode: CleanupMode,
+    job_level: bool,
 }
 
 impl Simulation {
@@ -46,6 +54,9 @@ impl Simulation {
             statistics: Statistics::new(),
             data_source,
             progress_bar,
+            trace_evictions: false,
+            cleanup_mode: CleanupMode::default(),
+            job_level: false,
         })
     }
 
@@ -53,12 +64,49 @@ impl Simulation {
         self.progress_bar.set_prefix(name);
     }
 
+    /// Records every eviction so it can be written out using `Statistics::write_eviction_trace`
+    pub fn enable_eviction_trace(&mut self) {
+        self.trace_evictions = true;
+    }
+
+    pub fn set_cleanup_mode(&mut self, cleanup_mode: CleanupMode) {
+        self.cleanup_mode = cleanup_mode;
+    }
+
+    /// Stores and evicts the artifacts of each job individually instead of whole pipelines
+    pub fn enable_job_level(&mut self) {
+        self.job_level = true;
+    }
+
+    fn check_job_level(&self, algorithm: &dyn CleanupAlgorithm) -> Result<()> {
+        if algorithm.evicts_jobs() && !self.job_level {
+            bail!("Job algorithms can only be used in job-level simulations (--job-level)");
+        }
+
+        Ok(())
+    }
+
     pub async fn run(
         mut self,
         algorithm: Box<dyn CleanupAlgorithm>,
+        rules: CleanupRules,
         storage_limit: ByteSize,
     ) -> Result<Statistics> {
+        let start = Instant::now();
+        self.check_job_level(algorithm.as_ref())?;
+        self.statistics.set_member_names(algorithm.member_names());
+
         let mut state = SimulationState::new(&self.data_source, algorithm, storage_limit);
+        state.set_cleanup_mode(self.cleanup_mode);
+        state.set_cleanup_rules(rules);
+
+        if self.job_level {
+            state.enable_job_level();
+        }
+
+        if self.trace_evictions {
+            state.enable_eviction_trace();
+        }
         let mut event_stream = self.data_source.events();
 
         let mut i = 0;
@@ -66,21 +114,75 @@ impl Simulation {
             state.process(event).await?;
             state.cleanup().await?;
             self.statistics.record(&state);
+            self.report_progress(&mut i);
+        }
+
+        self.progress_bar.finish();
+
+        self.statistics
+            .record_completion(start.elapsed(), state.take_eviction_trace());
+
+        Ok(self.statistics)
+    }
+
+    /// Simulates each tenant with its own instance of the algorithm and rules, the storage limit is split into
+    /// the tenant quotas and a shared pool
+    pub async fn run_tenants(
+        mut self,
+        tenants: Arc<TenantConfig>,
+        build_algorithm: impl Fn() -> (Box<dyn CleanupAlgorithm>, CleanupRules),
+        storage_limit: ByteSize,
+    ) -> Result<Statistics> {
+        let start = Instant::now();
+        let algorithms = (0..tenants.tenant_count())
+            .map(|_| build_algorithm())
+            .collect::<Vec<_>>();
 
-            // Don't waste immense resources on terminal I/O
-            i += 1;
-            if i > 250 {
-                self.progress_bar.inc(i);
-                self.progress_bar.set_message(&format!(
-                    "{:>5.2}% missed",
-                    self.statistics.current_miss_percentage() * 100.0
-                ));
-                i = 0;
-            }
+        self.check_job_level(algorithms[0].0.as_ref())?;
+        self.statistics
+            .set_member_names(algorithms[0].0.member_names());
+        self.statistics
+            .set_tenants(tenants.names(), tenants.quotas());
+
+        let mut state =
+            MultiTenantState::new(&self.data_source, tenants, algorithms, storage_limit)?;
+        state.set_cleanup_mode(self.cleanup_mode);
+
+        if self.job_level {
+            state.enable_job_level();
+        }
+
+        if self.trace_evictions {
+            state.enable_eviction_trace();
+        }
+        let mut event_stream = self.data_source.events();
+
+        let mut i = 0;
+        while let Some(event) = event_stream.try_next().await? {
+            state.process(event).await?;
+            state.cleanup().await?;
+            self.statistics.record_tenants(&state);
+            self.report_progress(&mut i);
         }
 
         self.progress_bar.finish();
 
+        self.statistics
+            .record_completion(start.elapsed(), state.take_eviction_trace());
+
         Ok(self.statistics)
     }
+
+    fn report_progress(&self, processed: &mut u64) {
+        // Don't waste immense resources on terminal I/O
+        *processed += 1;
+        if *processed > 250 {
+            self.progress_bar.inc(*processed);
+            self.progress_bar.set_message(&format!(
+                "{:>5.2}% missed",
+                self.statistics.current_miss_percentage() * 100.0
+            ));
+            *processed = 0;
+        }
+    }
 }
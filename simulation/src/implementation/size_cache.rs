@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use super::{PipelineID, SizeStrategy};
+
+/// Sampled pipeline sizes, pipelines without enough size samples are stored as `None`
+pub type SizeTable = HashMap<PipelineID, Option<i64>>;
+
+/// File next to the database which stores the sampled pipeline sizes for a given seed and size strategy.
+/// The cache is tied to the length and modification time of the database and discarded once either changes.
+pub struct SizeCache {
+    path: PathBuf,
+    fingerprint: String,
+}
+
+impl SizeCache {
+    pub fn for_database(database: &str, seed: u64, strategy: SizeStrategy) -> Result<Self> {
+        let database = Path::new(database.trim_start_matches("sqlite://"));
+        let metadata = fs::metadata(database)?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+
+        let mut file_name = database
+            .file_name()
+            .ok_or_else(|| anyhow!("Database path has no file name"))?
+            .to_owned();
+        file_name.push(format!(".sizes-{}-{}.csv", seed, strategy));
+
+        Ok(Self {
+            path: database.with_file_name(file_name),
+            fingerprint: format!("# {} {}", metadata.len(), modified.as_nanos()),
+        })
+    }
+
+    /// Reads the cached sizes, returns `None` if there is no cache or it belongs to a different version of the database
+    pub fn load(&self) -> Result<Option<SizeTable>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(_) => return Ok(None),
+        };
+
+        let mut lines = BufReader::new(file).lines();
+
+        if lines.next().transpose()?.as_deref() != Some(self.fingerprint.as_str()) {
+            return Ok(None);
+        }
+
+        let mut sizes = HashMap::new();
+
+        for line in lines {
+            let line = line?;
+            let (id, size) = line
+                .split_once(',')
+                .ok_or_else(|| anyhow!("Malformed size cache entry: '{}'", line))?;
+
+            let size = if size.is_empty() {
+                None
+            } else {
+                Some(size.parse()?)
+            };
+
+            sizes.insert(id.parse()?, size);
+        }
+
+        Ok(Some(sizes))
+    }
+
+    pub fn store(&self, sizes: &SizeTable) -> Result<()> {
+        let mut f = BufWriter::new(File::create(&self.path)?);
+
+        writeln!(f, "{}", self.fingerprint)?;
+
+        for (id, size) in sizes.iter() {
+            match size {
+                Some(size) => writeln!(f, "{},{}", id, size)?,
+                None => writeln!(f, "{},", id)?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,13 +1,22 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, str::FromStr};
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, Error, Result};
 use rand::{
     distributions::Uniform,
     prelude::{Distribution, StdRng},
     SeedableRng,
 };
+use rand_distr::LogNormal;
 use sqlx::SqlitePool;
 
+use super::{
+    hashing::{fnv1a, mix},
+    PipelineID,
+};
+
+/// Minimum number of samples required to derive the size of a job
+const MIN_SAMPLE_COUNT: usize = 31;
+
 fn split_first<'a>(from: &'a str, separator: &str) -> Option<(&'a str, &'a str)> {
     let mut components = from.split(separator).collect::<Vec<&str>>();
 
@@ -27,63 +36,216 @@ fn split_job<'a>(job: &'a str) -> Result<(String, &'a str)> {
         .ok_or_else(|| anyhow!("Unable to split job name: '{}'", job));
 }
 
+/// Derives the seed used for sampling a single job of a pipeline
+fn job_seed(seed: u64, pipeline: PipelineID, job_index: usize, job: &str) -> u64 {
+    [pipeline as u64, job_index as u64, fnv1a(job.bytes())]
+        .iter()
+        .fold(mix(seed), |state, value| mix(state ^ value))
+}
+
+/// How the size of a job is derived from the recorded samples of its environment and test suite
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeDistribution {
+    /// Resamples one of the recorded sizes
+    Empirical,
+    /// Samples from a log-normal distribution fitted to the recorded sizes
+    LogNormal,
+    /// Always uses the mean of the recorded sizes
+    Mean,
+    /// Always uses the median of the recorded sizes
+    Median,
+}
+
+impl FromStr for SizeDistribution {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "empirical" => Ok(Self::Empirical),
+            "log-normal" => Ok(Self::LogNormal),
+            "mean" => Ok(Self::Mean),
+            "median" => Ok(Self::Median),
+            _ => Err(anyhow!(
+                "Unknown size distribution '{}' (expected empirical, log-normal, mean or median)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SizeDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empirical => write!(f, "empirical"),
+            Self::LogNormal => write!(f, "log-normal"),
+            Self::Mean => write!(f, "mean"),
+            Self::Median => write!(f, "median"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SizeStrategy {
+    pub distribution: SizeDistribution,
+    /// Use the samples of all test suites within the same environment if a test suite does not have enough samples
+    pub environment_fallback: bool,
+}
+
+impl Default for SizeStrategy {
+    fn default() -> Self {
+        Self {
+            distribution: SizeDistribution::Empirical,
+            environment_fallback: false,
+        }
+    }
+}
+
+impl fmt::Display for SizeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.environment_fallback {
+            write!(f, "{}+environment", self.distribution)
+        } else {
+            write!(f, "{}", self.distribution)
+        }
+    }
+}
+
+enum JobDistribution {
+    Empirical(Vec<i64>, Uniform<usize>),
+    LogNormal(LogNormal<f64>),
+    Constant(i64),
+}
+
+impl JobDistribution {
+    fn fit(mut samples: Vec<i64>, distribution: SizeDistribution) -> Result<Self> {
+        let count = samples.len() as f64;
+
+        Ok(match distribution {
+            SizeDistribution::Empirical => {
+                let index_distribution = Uniform::new(0, samples.len());
+                Self::Empirical(samples, index_distribution)
+            }
+            SizeDistribution::LogNormal => {
+                let logarithms = samples
+                    .iter()
+                    .map(|s| ((*s).max(1) as f64).ln())
+                    .collect::<Vec<_>>();
+                let mean = logarithms.iter().sum::<f64>() / count;
+                let variance = logarithms.iter().map(|l| (l - mean).powi(2)).sum::<f64>() / count;
+
+                Self::LogNormal(LogNormal::new(mean, variance.sqrt())?)
+            }
+            SizeDistribution::Mean => Self::Constant(
+                (samples.iter().map(|s| *s as f64).sum::<f64>() / count).round() as i64,
+            ),
+            SizeDistribution::Median => {
+                samples.sort_unstable();
+                let middle = samples.len() / 2;
+
+                if samples.len().is_multiple_of(2) {
+                    Self::Constant((samples[middle - 1] + samples[middle]) / 2)
+                } else {
+                    Self::Constant(samples[middle])
+                }
+            }
+        })
+    }
+
+    fn sample(&self, rng: &mut StdRng) -> i64 {
+        match self {
+            Self::Empirical(samples, index_distribution) => samples[index_distribution.sample(rng)],
+            Self::LogNormal(distribution) => distribution.sample(rng).round() as i64,
+            Self::Constant(size) => *size,
+        }
+    }
+}
+
+/// Samples job sizes from the recorded JobSizeSamples.
+/// Each sample only depends on the seed, pipeline and job so the order of calls does not influence the results.
 pub struct JobSizeSampler {
-    rng: StdRng,
-    distributions: HashMap<String, Uniform<i64>>,
+    seed: u64,
+    strategy: SizeStrategy,
+    /// Fitted distributions by job, jobs without enough samples are stored as `None`
+    distributions: HashMap<String, Option<JobDistribution>>,
 }
 
 impl JobSizeSampler {
-    pub fn new(seed: u64) -> Self {
+    pub fn new(seed: u64, strategy: SizeStrategy) -> Self {
         Self {
-            rng: StdRng::seed_from_u64(seed),
+            seed,
+            strategy,
             distributions: HashMap::new(),
         }
     }
 
-    async fn sample_count(
+    /// Recorded sizes of the test suite or the whole environment if no test suite is given
+    async fn samples(
         &self,
         environment: &str,
-        test_suite: &str,
+        test_suite: Option<&str>,
         con: &SqlitePool,
-    ) -> Result<i64> {
-        let row: (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM JobSizeSample WHERE environment=$1 AND testSuite=$2",
-        )
-        .bind(environment)
-        .bind(test_suite)
-        .fetch_one(con)
-        .await?;
-
-        return Ok(row.0);
+    ) -> Result<Vec<i64>> {
+        let rows: Vec<(i64,)> = match test_suite {
+            Some(test_suite) => sqlx::query_as(
+                "SELECT bytes FROM JobSizeSample WHERE environment=$1 AND testSuite=$2 ORDER BY id",
+            )
+            .bind(environment)
+            .bind(test_suite)
+            .fetch_all(con)
+            .await?,
+            None => {
+                sqlx::query_as("SELECT bytes FROM JobSizeSample WHERE environment=$1 ORDER BY id")
+                    .bind(environment)
+                    .fetch_all(con)
+                    .await?
+            }
+        };
+
+        Ok(rows.into_iter().map(|r| r.0).collect())
     }
 
-    async fn ensure_distribution(&mut self, job: &str, con: &SqlitePool) -> Result<()> {
-        if !self.distributions.contains_key(job) {
-            let (environment, test_suite) = split_job(job)?;
-            let count = self.sample_count(&environment, test_suite, con).await?;
+    async fn fit_distribution(
+        &self,
+        job: &str,
+        con: &SqlitePool,
+    ) -> Result<Option<JobDistribution>> {
+        let (environment, test_suite) = split_job(job)?;
+        let mut samples = self.samples(&environment, Some(test_suite), con).await?;
 
-            if count <= 30 {
-                bail!("Not enough size samples available!");
-            }
+        if samples.len() < MIN_SAMPLE_COUNT && self.strategy.environment_fallback {
+            samples = self.samples(&environment, None, con).await?;
+        }
 
-            let distribution = Uniform::new(0, count);
-            self.distributions.insert(job.to_owned(), distribution);
+        if samples.len() < MIN_SAMPLE_COUNT {
+            return Ok(None);
         }
 
-        Ok(())
+        Ok(Some(JobDistribution::fit(
+            samples,
+            self.strategy.distribution,
+        )?))
     }
 
-    pub async fn sample(&mut self, job: &str, con: &SqlitePool) -> Result<i64> {
-        self.ensure_distribution(job, con).await?;
-        let distribution = self
-            .distributions
-            .get(job)
-            .ok_or_else(|| anyhow!("No distribution found for {}", job))?;
+    /// Samples the size of the job at position `job_index` within the jobs of the given pipeline
+    pub async fn sample(
+        &mut self,
+        pipeline: PipelineID,
+        job_index: usize,
+        job: &str,
+        con: &SqlitePool,
+    ) -> Result<i64> {
+        if !self.distributions.contains_key(job) {
+            let distribution = self.fit_distribution(job, con).await?;
+            self.distributions.insert(job.to_owned(), distribution);
+        }
 
-        let sample_index = distribution.sample(&mut self.rng);
-        let (environment, test_suite) = split_job(job)?;
-        let row: (i64,) = sqlx::query_as("SELECT bytes FROM JobSizeSample WHERE environment=$1 AND testSuite=$2 ORDER BY id LIMIT 1 OFFSET $3").bind(environment).bind(test_suite).bind(sample_index).fetch_one(con).await?;
+        let distribution = match self.distributions.get(job) {
+            Some(Some(distribution)) => distribution,
+            _ => bail!("Not enough size samples available!"),
+        };
+
+        let mut rng = StdRng::seed_from_u64(job_seed(self.seed, pipeline, job_index, job));
 
-        Ok(row.0)
+        Ok(distribution.sample(&mut rng))
     }
 }
@@ -1,33 +1,148 @@
 use super::{
-    data_source::{DataSource, MergeRequestEvent, SimulationEvent, SimulationEventKind},
-    CleanupAlgorithm, CleanupDataSource, PipelineID,
+    data_source::{
+        AccessLogEntry, DataSource, MergeRequestEvent, PipelineStatus, SimulationEvent,
+        SimulationEventKind,
+    },
+    eviction_trace::{EvictionRecord, EvictionTrace},
+    jobs::job_index,
+    protection::ProtectionCandidate,
+    CleanupAlgorithm, CleanupDataSource, CleanupMode, CleanupRules, Eviction, PipelineID,
+    StateChange,
 };
 use anyhow::{anyhow, bail, Result};
 use bytesize::ByteSize;
-use std::collections::{BTreeSet, HashMap};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    hash::Hash,
+};
+
+/// Status of a pipeline and whether it has been merged, the only properties static scoring components may depend on
+pub type ScoringClass = (PipelineStatus, bool);
+
+/// Evictions performed on behalf of a single member of the algorithm chain
+#[derive(Clone, Copy, PartialEq)]
+pub struct MemberEvictions {
+    pub count: u32,
+    pub bytes: ByteSize,
+}
+
+/// Effects of the retention policies, independent of the evictions made by the algorithm chain
+#[derive(Clone, Copy, Default)]
+pub struct RetentionStatistics {
+    pub expired_count: u32,
+    pub expired_bytes: ByteSize,
+    /// Size of expired pipelines which have not been accessed while they were stored
+    pub unused_bytes: ByteSize,
+    /// Accesses to pipelines after they have been expired
+    pub missed_access_count: u32,
+    /// Size of the expired pipelines summed up for each missed access
+    pub redownload_bytes: ByteSize,
+}
+
+/// Artifacts of a single job of a stored pipeline, only tracked when jobs are evicted individually
+pub struct StoredJob {
+    /// Name of the job in the format `<environment>:<testSuite>`
+    pub name: String,
+    pub size: ByteSize,
+    /// Whether the artifacts of the job are still stored
+    pub is_stored: bool,
+}
 
 pub struct SimulationState {
     pub latest_event: Option<SimulationEvent>,
 
     data_source: DataSource,
     algorithm: Box<dyn CleanupAlgorithm>,
+    rules: CleanupRules,
 
     pub storage_limit: ByteSize,
     pub occupied_storage: ByteSize,
     pub stored_pipelines: BTreeSet<PipelineID>,
+    /// Stored pipelines which are not protected, only these are part of the eviction indices below
+    pub evictable_pipelines: BTreeSet<PipelineID>,
+    /// Stored pipelines exempt from eviction by the protection rules
+    pub protected_pipelines: HashSet<PipelineID>,
+    /// Protected pipelines ordered by the time at which their protection by age runs out
+    protection_expiry: BTreeSet<(i64, PipelineID)>,
+    /// Stored pipelines which have not finished yet, their artifacts are not part of the occupied storage
+    running_pipelines: HashSet<PipelineID>,
+    /// Occupied storage of all protected pipelines which have finished
+    pub protected_storage: ByteSize,
+    /// Accesses to pipelines which were protected at the time of the access, some of them might have been stored anyway
+    pub protected_access_count: u32,
+
+    cleanup_mode: CleanupMode,
+    /// Timestamp at which the next periodic cleanup run is scheduled
+    next_cleanup: Option<i64>,
+    /// Number of cleanup runs which evicted at least one pipeline
+    pub cleanup_runs: u32,
+    /// Simulated time in seconds during which the occupied storage exceeded the storage limit
+    pub over_limit_duration: i64,
 
     pub access_count: u32,
     pub access_count_missed: u32,
     pub deleted_count: u32,
 
+    /// Evictions indexed by the chain member of the algorithm which selected them
+    pub evictions_by_member: Vec<MemberEvictions>,
+
+    /// Size of all pipelines that were missed at least once, counted once per pipeline
+    pub missed_bytes: ByteSize,
+    /// Size of the missed pipeline summed up for each missed access (cost of re-downloading)
+    pub redownload_bytes: ByteSize,
+    /// Duration in seconds it takes to re-run all pipelines that were missed at least once
+    pub rerun_duration: i64,
+    /// IDs of pipelines which have been missed at least once
+    pub missed_pipelines: HashSet<PipelineID>,
+
     /// Timestamps of accesses to each pipeline
     pub accesses: HashMap<PipelineID, Vec<i64>>,
 
-    /// IDs of merged pipelines in chronological order
+    /// IDs of merged evictable pipelines in chronological order
     pub merges: BTreeSet<PipelineID>,
+    /// Timestamps of when a stored pipeline has been merged, including protected ones
+    pub merge_times: HashMap<PipelineID, i64>,
+    /// Evictable merged pipelines ordered by the time they have been merged
+    pub merge_time_index: BTreeSet<(i64, PipelineID)>,
 
     /// Timestamps of when a pipeline was created
     pub storage_times: HashMap<PipelineID, i64>,
+
+    /// Evictable pipelines with at least one access ordered by their latest access
+    pub last_access_index: BTreeSet<(i64, PipelineID)>,
+    /// Evictable pipelines which have not been accessed yet
+    pub unaccessed_pipelines: BTreeSet<PipelineID>,
+    /// Evictable pipelines ordered by their size
+    pub size_index: BTreeSet<(ByteSize, PipelineID)>,
+    /// Evictable pipelines grouped by their status and whether they have been merged.
+    /// Pipelines without a known status are not part of any group.
+    pub scoring_classes: HashMap<ScoringClass, BTreeSet<PipelineID>>,
+    /// Evictable pipelines grouped by their status (if known) ordered by their storage time
+    pub storage_time_index: HashMap<Option<PipelineStatus>, BTreeSet<(i64, PipelineID)>>,
+    /// Refs of stored pipelines, pipelines with an unknown ref are omitted
+    pub refs: HashMap<PipelineID, String>,
+    /// Stored pipelines grouped by their ref ordered by their storage time
+    pub ref_index: HashMap<String, BTreeSet<(i64, PipelineID)>>,
+    /// Latest pipeline stored for each ref, kept after the pipeline has been removed
+    pub latest_of_ref: HashMap<String, PipelineID>,
+    /// Evictable pipelines superseded by a newer pipeline of the same ref ordered by their storage time
+    pub superseded_index: BTreeSet<(i64, PipelineID)>,
+
+    pub retention: RetentionStatistics,
+    /// IDs of pipelines which have been removed by a retention policy
+    pub expired_pipelines: HashSet<PipelineID>,
+
+    /// Log of evictions made by the algorithm, only recorded if enabled
+    eviction_trace: Option<EvictionTrace>,
+
+    /// Whether the artifacts of jobs are stored and evicted individually instead of whole pipelines
+    job_level: bool,
+    /// Jobs of each stored pipeline, only tracked in job-level mode
+    pub jobs: HashMap<PipelineID, Vec<StoredJob>>,
+    /// Stored jobs of evictable pipelines ordered by their size alongside their pipeline and index
+    pub job_size_index: BTreeSet<(ByteSize, PipelineID, usize)>,
+    /// Jobs which have been missed at least once, identified by their pipeline and index
+    missed_jobs: HashSet<(PipelineID, usize)>,
 }
 
 impl SimulationState {
@@ -36,59 +151,732 @@ impl SimulationState {
         algorithm: Box<dyn CleanupAlgorithm>,
         storage_limit: ByteSize,
     ) -> Self {
+        let evictions_by_member = vec![
+            MemberEvictions {
+                count: 0,
+                bytes: ByteSize::b(0),
+            };
+            algorithm.member_names().len()
+        ];
+
         Self {
             latest_event: None,
             data_source: data_source.clone(),
             algorithm,
+            rules: CleanupRules::default(),
             storage_limit,
             occupied_storage: ByteSize::b(0),
             stored_pipelines: BTreeSet::new(),
+            evictable_pipelines: BTreeSet::new(),
+            protected_pipelines: HashSet::new(),
+            protection_expiry: BTreeSet::new(),
+            running_pipelines: HashSet::new(),
+            protected_storage: ByteSize::b(0),
+            protected_access_count: 0,
+            cleanup_mode: CleanupMode::default(),
+            next_cleanup: None,
+            cleanup_runs: 0,
+            over_limit_duration: 0,
             access_count: 0,
             access_count_missed: 0,
             deleted_count: 0,
+            evictions_by_member,
+            missed_bytes: ByteSize::b(0),
+            redownload_bytes: ByteSize::b(0),
+            rerun_duration: 0,
+            missed_pipelines: HashSet::new(),
             accesses: HashMap::new(),
             merges: BTreeSet::new(),
+            merge_times: HashMap::new(),
+            merge_time_index: BTreeSet::new(),
             storage_times: HashMap::new(),
+            last_access_index: BTreeSet::new(),
+            unaccessed_pipelines: BTreeSet::new(),
+            size_index: BTreeSet::new(),
+            scoring_classes: HashMap::new(),
+            storage_time_index: HashMap::new(),
+            refs: HashMap::new(),
+            ref_index: HashMap::new(),
+            latest_of_ref: HashMap::new(),
+            superseded_index: BTreeSet::new(),
+            retention: RetentionStatistics::default(),
+            expired_pipelines: HashSet::new(),
+            eviction_trace: None,
+            job_level: false,
+            jobs: HashMap::new(),
+            job_size_index: BTreeSet::new(),
+            missed_jobs: HashSet::new(),
         }
     }
 
-    async fn remove_pipeline(&mut self, id: &PipelineID) -> Result<bool> {
+    /// Stores and evicts the artifacts of each job individually, misses are counted per accessed job
+    pub fn enable_job_level(&mut self) {
+        self.job_level = true;
+    }
+
+    pub fn enable_eviction_trace(&mut self) {
+        self.eviction_trace = Some(EvictionTrace::new());
+    }
+
+    pub fn take_eviction_trace(&mut self) -> Option<EvictionTrace> {
+        self.eviction_trace.take()
+    }
+
+    pub fn set_cleanup_mode(&mut self, cleanup_mode: CleanupMode) {
+        self.cleanup_mode = cleanup_mode;
+    }
+
+    /// Applies retention policies and protection rules, has to be called before any event is processed
+    pub fn set_cleanup_rules(&mut self, rules: CleanupRules) {
+        self.rules = rules;
+    }
+
+    /// Removes the pipeline from storage and returns the amount of storage freed if it was present
+    async fn remove_pipeline(&mut self, id: &PipelineID) -> Result<Option<ByteSize>> {
         let was_present = self.stored_pipelines.remove(id);
-        self.merges.remove(id);
 
         if was_present {
+            let size = self.stored_size(*id).await?;
+
+            self.unindex_evictable(*id, size).await;
+            self.jobs.remove(id);
+            let was_running = self.running_pipelines.remove(id);
+            if self.protected_pipelines.remove(id) && !was_running {
+                self.protected_storage =
+                    ByteSize::b(self.protected_storage.as_u64() - size.as_u64());
+            }
+            self.merge_times.remove(id);
+
+            let mut pipeline_ref = None;
+            if let Some(storage_time) = self.storage_times.get(id) {
+                let entry = (*storage_time, *id);
+
+                pipeline_ref = self.refs.remove(id);
+                if let Some(pipeline_ref) = pipeline_ref.as_ref() {
+                    remove_from_group(&mut self.ref_index, pipeline_ref, &entry);
+                }
+            }
+
             self.deleted_count += 1;
+            let timestamp = self.latest_event.map(|e| e.timestamp).unwrap_or(0);
+            self.algorithm
+                .observe(
+                    StateChange::Removed { id: *id, timestamp },
+                    &self.data_source,
+                )
+                .await;
             // TODO This is really ugly. Fix it by implementing the sub and sub-assign traits.
-            self.occupied_storage = ByteSize::b(
-                self.occupied_storage.as_u64()
-                    - self.data_source.size_of_pipeline(*id).await?.as_u64(),
+            self.occupied_storage = ByteSize::b(self.occupied_storage.as_u64() - size.as_u64());
+
+            if let Some(pipeline_ref) = pipeline_ref {
+                self.update_ref_protection(&pipeline_ref).await?;
+            }
+
+            Ok(Some(size))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Evicts the artifacts of a single job and returns the amount of storage freed if they were present.
+    /// The pipeline is removed entirely once none of its jobs are stored anymore.
+    async fn remove_job(&mut self, id: PipelineID, index: usize) -> Result<Option<ByteSize>> {
+        let (size, is_last) = match self.jobs.get(&id) {
+            Some(jobs) if matches!(jobs.get(index), Some(job) if job.is_stored) => (
+                jobs[index].size,
+                jobs.iter()
+                    .enumerate()
+                    .all(|(i, job)| i == index || !job.is_stored),
+            ),
+            _ => return Ok(None),
+        };
+
+        if is_last {
+            return self.remove_pipeline(&id).await;
+        }
+
+        let stored_size = self.stored_size(id).await?;
+        let remaining_size = ByteSize::b(stored_size.as_u64() - size.as_u64());
+
+        if self.evictable_pipelines.contains(&id) {
+            self.size_index.remove(&(stored_size, id));
+            self.size_index.insert((remaining_size, id));
+            self.job_size_index.remove(&(size, id, index));
+            self.observe_indexed(id, remaining_size).await;
+        } else if self.protected_pipelines.contains(&id) && !self.running_pipelines.contains(&id) {
+            self.protected_storage = ByteSize::b(self.protected_storage.as_u64() - size.as_u64());
+        }
+
+        if let Some(jobs) = self.jobs.get_mut(&id) {
+            jobs[index].is_stored = false;
+        }
+        self.occupied_storage = ByteSize::b(self.occupied_storage.as_u64() - size.as_u64());
+
+        Ok(Some(size))
+    }
+
+    /// Size of the artifacts of a stored pipeline which have not been evicted yet
+    async fn stored_size(&self, id: PipelineID) -> Result<ByteSize> {
+        match self.jobs.get(&id) {
+            Some(jobs) => Ok(jobs
+                .iter()
+                .filter(|job| job.is_stored)
+                .fold(ByteSize::b(0), |sum, job| sum + job.size)),
+            None => self.data_source.size_of_pipeline(id).await,
+        }
+    }
+
+    /// Adds a newly stored pipeline to the ref index and, unless it is protected, to the eviction indices
+    async fn index_pipeline(
+        &mut self,
+        id: PipelineID,
+        size: ByteSize,
+        storage_time: i64,
+    ) -> Result<()> {
+        let pipeline_ref = self.data_source.ref_of_pipeline(id).await.ok().flatten();
+
+        if let Some(pipeline_ref) = pipeline_ref.as_ref() {
+            self.ref_index
+                .entry(pipeline_ref.clone())
+                .or_default()
+                .insert((storage_time, id));
+            self.refs.insert(id, pipeline_ref.clone());
+
+            let previous = self.latest_of_ref.insert(pipeline_ref.clone(), id);
+            if let Some(previous) = previous.filter(|p| self.evictable_pipelines.contains(p)) {
+                let storage_time = self.storage_times.get(&previous).copied().unwrap_or(0);
+                self.superseded_index.insert((storage_time, previous));
+            }
+        }
+
+        self.index_evictable(id, size).await;
+        self.update_protection(id).await?;
+
+        if let Some(pipeline_ref) = pipeline_ref {
+            self.update_ref_protection(&pipeline_ref).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes a stored pipeline visible to the cleanup algorithms and retention policies
+    async fn index_evictable(&mut self, id: PipelineID, size: ByteSize) {
+        if !self.evictable_pipelines.insert(id) {
+            return;
+        }
+
+        match self.accesses.get(&id).and_then(|a| a.last()) {
+            Some(last_access) => self.last_access_index.insert((*last_access, id)),
+            None => self.unaccessed_pipelines.insert(id),
+        };
+        self.size_index.insert((size, id));
+
+        if let Some(jobs) = self.jobs.get(&id) {
+            for (index, job) in jobs.iter().enumerate().filter(|(_, job)| job.is_stored) {
+                self.job_size_index.insert((job.size, id, index));
+            }
+        }
+
+        let is_merged = self.merge_times.contains_key(&id);
+        let status = self.data_source.status_of_pipeline(id).await.ok();
+        if let Some(status) = status {
+            self.scoring_classes
+                .entry((status, is_merged))
+                .or_default()
+                .insert(id);
+        }
+
+        if let Some(storage_time) = self.storage_times.get(&id) {
+            self.storage_time_index
+                .entry(status)
+                .or_default()
+                .insert((*storage_time, id));
+
+            if self.is_superseded(id) {
+                self.superseded_index.insert((*storage_time, id));
+            }
+        }
+
+        if let Some(merge_time) = self.merge_times.get(&id) {
+            self.merges.insert(id);
+            self.merge_time_index.insert((*merge_time, id));
+        }
+
+        self.observe_indexed(id, size).await;
+    }
+
+    /// Notifies the algorithm about an evictable pipeline with the given stored size
+    async fn observe_indexed(&self, id: PipelineID, size: ByteSize) {
+        let stored_at = self.storage_times.get(&id).copied().unwrap_or(0);
+        let accesses = self.accesses.get(&id).map_or(&[][..], |a| &a[..]);
+
+        self.algorithm
+            .observe(
+                StateChange::Indexed {
+                    id,
+                    size,
+                    stored_at,
+                    accesses,
+                },
+                &self.data_source,
+            )
+            .await;
+    }
+
+    /// Hides a stored pipeline from the cleanup algorithms and retention policies
+    async fn unindex_evictable(&mut self, id: PipelineID, size: ByteSize) {
+        if !self.evictable_pipelines.remove(&id) {
+            return;
+        }
+
+        if let Some(last_access) = self.accesses.get(&id).and_then(|a| a.last()) {
+            self.last_access_index.remove(&(*last_access, id));
+        }
+        self.unaccessed_pipelines.remove(&id);
+        self.size_index.remove(&(size, id));
+
+        if let Some(jobs) = self.jobs.get(&id) {
+            for (index, job) in jobs.iter().enumerate() {
+                self.job_size_index.remove(&(job.size, id, index));
+            }
+        }
+
+        let is_merged = self.merge_times.contains_key(&id);
+        let status = self.data_source.status_of_pipeline(id).await.ok();
+        if let Some(status) = status {
+            self.remove_from_scoring_class((status, is_merged), id);
+        }
+
+        if let Some(storage_time) = self.storage_times.get(&id) {
+            remove_from_group(&mut self.storage_time_index, &status, &(*storage_time, id));
+            self.superseded_index.remove(&(*storage_time, id));
+        }
+
+        self.merges.remove(&id);
+        if let Some(merge_time) = self.merge_times.get(&id) {
+            self.merge_time_index.remove(&(*merge_time, id));
+        }
+
+        self.algorithm
+            .observe(StateChange::Unindexed(id), &self.data_source)
+            .await;
+    }
+
+    /// Whether a newer pipeline of the same ref has been stored since the pipeline, even if it has been removed again
+    pub fn is_superseded(&self, id: PipelineID) -> bool {
+        matches!(
+            self.refs.get(&id).and_then(|r| self.latest_of_ref.get(r)),
+            Some(latest) if *latest != id
+        )
+    }
+
+    fn remove_from_scoring_class(&mut self, class: ScoringClass, id: PipelineID) {
+        remove_from_group(&mut self.scoring_classes, &class, &id);
+    }
+
+    /// Evaluates the protection rules for a stored pipeline and hides it from or exposes it to the eviction indices
+    async fn update_protection(&mut self, id: PipelineID) -> Result<()> {
+        if !self.stored_pipelines.contains(&id) || self.rules.protection_rules().is_empty() {
+            return Ok(());
+        }
+
+        let status = self.data_source.status_of_pipeline(id).await.ok();
+        let storage_time = self.storage_times.get(&id).copied().unwrap_or(0);
+        let pipeline_ref = self.refs.get(&id).map(|r| r.as_str());
+        let max_limit = self.rules.latest_limits().max().unwrap_or(0);
+
+        let candidate = ProtectionCandidate {
+            id,
+            pipeline_ref,
+            status,
+            age: self.latest_event.map(|e| e.timestamp).unwrap_or(0) - storage_time,
+            rank: pipeline_ref
+                .and_then(|r| self.ref_index.get(r))
+                .map(|pipelines| {
+                    pipelines
+                        .iter()
+                        .rev()
+                        .take(max_limit)
+                        .position(|(_, p)| *p == id)
+                        .unwrap_or(max_limit)
+                }),
+        };
+
+        // Protection may only end once all matching rules limited by age have run out
+        let mut is_protected = false;
+        let mut protected_until = Some(i64::MIN);
+        for rule in self.rules.protection_rules() {
+            if rule.matches(&candidate) {
+                is_protected = true;
+                protected_until = match (protected_until, rule.max_age) {
+                    (Some(until), Some(max_age)) => Some(until.max(storage_time + max_age)),
+                    _ => None,
+                };
+            }
+        }
+
+        let size = self.stored_size(id).await?;
+        let is_running = self.running_pipelines.contains(&id);
+
+        if is_protected {
+            if let Some(protected_until) = protected_until {
+                self.protection_expiry.insert((protected_until, id));
+            }
+
+            if self.protected_pipelines.insert(id) {
+                self.unindex_evictable(id, size).await;
+                if !is_running {
+                    self.protected_storage += size;
+                }
+            }
+        } else if self.protected_pipelines.remove(&id) {
+            if !is_running {
+                self.protected_storage =
+                    ByteSize::b(self.protected_storage.as_u64() - size.as_u64());
+            }
+            self.index_evictable(id, size).await;
+        }
+
+        Ok(())
+    }
+
+    /// Re-evaluates the protection of stored pipelines of the ref after one of them has been stored or removed,
+    /// as rules may only protect the latest ones.
+    /// This shifts the ranks by at most one, so only the pipelines right next to a limit may have crossed it.
+    async fn update_ref_protection(&mut self, pipeline_ref: &str) -> Result<()> {
+        let limits = self.rules.latest_limits().collect::<Vec<_>>();
+
+        let ids = match (self.ref_index.get(pipeline_ref), limits.iter().max()) {
+            (Some(pipelines), Some(max_limit)) => pipelines
+                .iter()
+                .rev()
+                .take(max_limit + 1)
+                .enumerate()
+                .filter(|(rank, _)| limits.iter().any(|l| *rank == *l || rank + 1 == *l))
+                .map(|(_, (_, id))| *id)
+                .collect::<Vec<_>>(),
+            _ => return Ok(()),
+        };
+
+        for id in ids {
+            self.update_protection(id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Re-evaluates pipelines whose protection by age has run out
+    async fn release_expired_protection(&mut self) -> Result<()> {
+        let now = self.latest_event.map(|e| e.timestamp).unwrap_or(0);
+
+        while let Some((protected_until, id)) = self.protection_expiry.iter().next().copied() {
+            if protected_until >= now {
+                break;
+            }
+
+            self.protection_expiry.remove(&(protected_until, id));
+            self.update_protection(id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Marks a stored pipeline as merged and moves it into the corresponding scoring class
+    async fn merge_pipeline(&mut self, id: PipelineID, timestamp: i64) {
+        if self.merge_times.contains_key(&id) {
+            return;
+        }
+
+        self.merge_times.insert(id, timestamp);
+
+        if !self.evictable_pipelines.contains(&id) {
+            return;
+        }
+
+        self.merges.insert(id);
+        self.merge_time_index.insert((timestamp, id));
+
+        if let Ok(status) = self.data_source.status_of_pipeline(id).await {
+            self.remove_from_scoring_class((status, false), id);
+            self.scoring_classes
+                .entry((status, true))
+                .or_default()
+                .insert(id);
+        }
+    }
+
+    async fn record_access(&mut self, id: PipelineID, timestamp: i64) -> Result<()> {
+        let accesses = self.accesses.entry(id).or_default();
+        let previous_access = accesses.last().copied();
+        accesses.push(timestamp);
+
+        if self.evictable_pipelines.contains(&id) {
+            match previous_access {
+                Some(previous_access) => self.last_access_index.remove(&(previous_access, id)),
+                None => self.unaccessed_pipelines.remove(&id),
+            };
+            self.last_access_index.insert((timestamp, id));
+
+            let size = self.stored_size(id).await?;
+            self.observe_indexed(id, size).await;
+        }
+
+        Ok(())
+    }
+
+    /// Jobs required by an access which are not stored, `None` unless jobs are stored individually.
+    /// Accesses which can not be attributed to a single job require all jobs of the pipeline.
+    async fn missing_jobs(&self, entry: &AccessLogEntry) -> Result<Option<Vec<usize>>> {
+        if !self.job_level {
+            return Ok(None);
+        }
+
+        let jobs = self.data_source.jobs_of_pipeline(entry.pipeline).await?;
+        let required = match job_index(
+            jobs.iter().map(|(name, _)| name.as_str()),
+            entry.job.as_deref(),
+        ) {
+            Some(index) => vec![index],
+            None => (0..jobs.len()).collect(),
+        };
+
+        let stored_jobs = self.jobs.get(&entry.pipeline);
+        Ok(Some(
+            required
+                .into_iter()
+                .filter(|index| !matches!(stored_jobs, Some(stored) if stored[*index].is_stored))
+                .collect(),
+        ))
+    }
+
+    /// Accounts for a missed access, either to the whole pipeline or, in job-level mode, to the given missing jobs
+    async fn record_miss(
+        &mut self,
+        id: PipelineID,
+        missing_jobs: Option<Vec<usize>>,
+    ) -> Result<()> {
+        let jobs = match missing_jobs {
+            Some(_) => Some(self.data_source.jobs_of_pipeline(id).await?),
+            None => None,
+        };
+        let size = match (&missing_jobs, &jobs) {
+            (Some(missing_jobs), Some(jobs)) => missing_jobs
+                .iter()
+                .fold(ByteSize::b(0), |sum, index| sum + jobs[*index].1),
+            _ => self.data_source.size_of_pipeline(id).await?,
+        };
+
+        self.access_count_missed += 1;
+
+        if let Some(trace) = self.eviction_trace.as_mut() {
+            trace.mark_regretted(id);
+        }
+
+        self.redownload_bytes += size;
+
+        if self.expired_pipelines.contains(&id) {
+            self.retention.missed_access_count += 1;
+            self.retention.redownload_bytes += size;
+        }
+
+        let is_first_miss = self.missed_pipelines.insert(id);
+
+        // Missed bytes count each pipeline or, in job-level mode, each job only once
+        match (&missing_jobs, &jobs) {
+            (Some(missing_jobs), Some(jobs)) => {
+                for index in missing_jobs {
+                    if self.missed_jobs.insert((id, *index)) {
+                        self.missed_bytes += jobs[*index].1;
+                    }
+                }
+            }
+            _ if is_first_miss => self.missed_bytes += size,
+            _ => {}
+        }
+
+        if is_first_miss {
+            self.rerun_duration += self.data_source.duration_of_pipeline(id).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn trace_eviction(&mut self, id: PipelineID, size: ByteSize, algorithm: String) {
+        let timestamp = self.latest_event.map(|e| e.timestamp).unwrap_or(0);
+
+        let record = EvictionRecord {
+            timestamp,
+            pipeline: id,
+            size,
+            // Tracing must not fail runs on traces without statuses
+            status: self.data_source.status_of_pipeline(id).await.ok(),
+            age: self.storage_times.get(&id).map_or(0, |t| timestamp - t),
+            access_count: self.accesses.get(&id).map_or(0, |a| a.len()),
+            algorithm,
+            regretted: false,
+        };
+
+        if let Some(trace) = self.eviction_trace.as_mut() {
+            trace.record(record);
+        }
+    }
+
+    /// Removes all pipelines expired by the retention policies
+    async fn expire(&mut self) -> Result<()> {
+        let event = match self.latest_event {
+            Some(event) => event,
+            None => return Ok(()),
+        };
+
+        let data_source = CleanupDataSource::new(self, &self.data_source);
+        let expired = self.rules.expired_pipelines(&data_source, &event).await;
+
+        for (id, policy) in expired {
+            // Multiple policies may expire the same pipeline and protected pipelines never expire.
+            // Running pipelines do not occupy storage yet, they may only expire once they have finished.
+            if !self.evictable_pipelines.contains(&id) || self.running_pipelines.contains(&id) {
+                continue;
+            }
+
+            if self.eviction_trace.is_some() {
+                let algorithm = self.rules.retention_policy_name(policy).to_owned();
+                let size = self.stored_size(id).await?;
+                self.trace_eviction(id, size, algorithm).await;
+            }
+
+            let storage_time = self.storage_times.get(&id).copied().unwrap_or(0);
+            let was_used = matches!(
+                self.accesses.get(&id).and_then(|a| a.last()),
+                Some(last_access) if *last_access >= storage_time
             );
+
+            if let Some(size) = self.remove_pipeline(&id).await? {
+                self.retention.expired_count += 1;
+                self.retention.expired_bytes += size;
+
+                if !was_used {
+                    self.retention.unused_bytes += size;
+                }
+
+                self.expired_pipelines.insert(id);
+            }
         }
 
-        Ok(was_present)
+        Ok(())
+    }
+
+    /// Pipeline and size of the artifacts an eviction would free, `None` if they are not stored
+    async fn eviction_size(&self, eviction: Eviction) -> Result<Option<(PipelineID, ByteSize)>> {
+        match eviction {
+            Eviction::Pipeline(id) if self.stored_pipelines.contains(&id) => {
+                Ok(Some((id, self.stored_size(id).await?)))
+            }
+            Eviction::Pipeline(_) => Ok(None),
+            Eviction::Job(id, index) => Ok(self
+                .jobs
+                .get(&id)
+                .and_then(|jobs| jobs.get(index))
+                .filter(|job| job.is_stored)
+                .map(|job| (id, job.size))),
+        }
+    }
+
+    /// Whether the scheduled time of a periodic cleanup run has been reached
+    fn is_cleanup_scheduled(&mut self, interval: i64) -> bool {
+        let now = self.latest_event.map(|e| e.timestamp).unwrap_or(0);
+        let is_scheduled = matches!(self.next_cleanup, Some(next_cleanup) if now >= next_cleanup);
+
+        // Runs are aligned to multiples of the interval like a cron job would be
+        if is_scheduled || self.next_cleanup.is_none() {
+            self.next_cleanup = Some((now.div_euclid(interval) + 1) * interval);
+        }
+
+        is_scheduled
     }
 
     pub async fn cleanup(&mut self) -> Result<()> {
-        let mut i = 0;
+        let is_due = match self.cleanup_mode {
+            CleanupMode::Periodic { interval, .. } => self.is_cleanup_scheduled(interval),
+            mode => matches!(
+                mode.trigger(self.storage_limit),
+                Some(trigger) if self.occupied_storage > trigger
+            ),
+        };
+        let target = self.cleanup_mode.target(self.storage_limit);
+
+        if !is_due || self.occupied_storage <= target {
+            return Ok(());
+        }
 
-        while self.is_over_limit() {
+        let mut has_evicted = false;
+        let mut unproductive_iterations = 0;
+
+        while self.occupied_storage > target && !self.evictable_pipelines.is_empty() {
             let data_source = CleanupDataSource::new(self, &self.data_source);
-            let pipeline_to_purge = self.algorithm.select_pipeline(&data_source).await;
-            self.remove_pipeline(&pipeline_to_purge).await?;
+            let (eviction, member) = self.algorithm.select_eviction(&data_source).await;
 
-            if i > 10_000 {
-                bail!(
-                    "Algorithm did not manage to get below limit after {} iterations",
-                    i
-                );
+            if self.eviction_trace.is_some() {
+                if let Some((id, size)) = self.eviction_size(eviction).await? {
+                    let algorithm = self.algorithm.member_names()[member].clone();
+                    self.trace_eviction(id, size, algorithm).await;
+                }
+            }
+
+            let freed = match eviction {
+                Eviction::Pipeline(id) => self.remove_pipeline(&id).await?,
+                Eviction::Job(id, index) => self.remove_job(id, index).await?,
+            };
+
+            if let Some(size) = freed {
+                // Runs are only counted once they actually evicted something
+                if !has_evicted {
+                    self.cleanup_runs += 1;
+                    has_evicted = true;
+                }
+
+                let evictions = &mut self.evictions_by_member[member];
+                evictions.count += 1;
+                evictions.bytes += size;
+                unproductive_iterations = 0;
+            } else {
+                // Batch cleanup runs may legitimately require a large number of evictions,
+                // only bail if the algorithm repeatedly selects pipelines which are not stored
+                unproductive_iterations += 1;
+
+                if unproductive_iterations > 10_000 {
+                    bail!(
+                        "Algorithm did not select a stored pipeline in {} consecutive iterations",
+                        unproductive_iterations
+                    );
+                }
             }
-            i += 1;
         }
 
         Ok(())
     }
 
+    /// Accounts for the simulated time which passed since the latest event
+    fn advance_clock(&mut self, event: &SimulationEvent) {
+        if let Some(latest_event) = self.latest_event {
+            if self.is_over_limit() {
+                self.over_limit_duration += event.timestamp - latest_event.timestamp;
+            }
+        }
+    }
+
+    /// Moves the simulated time forward to an event without processing it, used for events which
+    /// concern pipelines of other tenants. Retention policies still get the chance to expire pipelines.
+    pub async fn advance(&mut self, event: SimulationEvent) -> Result<()> {
+        self.advance_clock(&event);
+        self.latest_event = Some(event);
+
+        self.release_expired_protection().await?;
+        self.expire().await
+    }
+
     pub async fn process(&mut self, event: SimulationEvent) -> Result<()> {
+        self.advance_clock(&event);
+
         match event.kind {
             SimulationEventKind::MergeRequestEvent => {
                 let mr_event: MergeRequestEvent =
@@ -102,7 +890,7 @@ impl SimulationState {
                     {
                         for pipeline in pipelines {
                             if self.stored_pipelines.contains(&pipeline.id) {
-                                self.merges.insert(pipeline.id);
+                                self.merge_pipeline(pipeline.id, event.timestamp).await;
                             }
                         }
                     }
@@ -118,28 +906,35 @@ impl SimulationState {
                         {
                             self.access_count += 1;
 
-                            if !self.stored_pipelines.contains(&entry.pipeline) {
-                                self.access_count_missed += 1;
+                            if self.protected_pipelines.contains(&entry.pipeline) {
+                                self.protected_access_count += 1;
+                            }
+
+                            let missing_jobs = self.missing_jobs(&entry).await?;
+                            let is_miss = match &missing_jobs {
+                                Some(missing_jobs) => !missing_jobs.is_empty(),
+                                None => !self.stored_pipelines.contains(&entry.pipeline),
+                            };
+
+                            if is_miss {
+                                self.record_miss(entry.pipeline, missing_jobs).await?;
+                                self.algorithm
+                                    .observe(StateChange::Missed(entry.pipeline), &self.data_source)
+                                    .await;
                                 // eprintln!(
                                 //     "Missed access {} to pipeline {}",
                                 //     event.key, entry.pipeline
                                 // );
                             }
 
-                            if let Some(accesses) = self.accesses.get_mut(&entry.pipeline) {
-                                accesses.push(entry.timestamp);
-                            } else {
-                                self.accesses.insert(entry.pipeline, vec![entry.timestamp]);
-                            }
+                            self.record_access(entry.pipeline, entry.timestamp).await?;
                         }
                     }
                     Err(e) => eprintln!("Failed to locate access log entry: {:?}", e),
                 }
             }
             SimulationEventKind::PipelineCreated => {
-                if let Err(_) = self.data_source.size_of_pipeline(event.key).await {
-                    // println!("Skipping pipeline due to unavailable size samples.");
-                } else {
+                if let Ok(size) = self.data_source.size_of_pipeline(event.key).await {
                     // println!("Taking pipeline");
                     if !self.stored_pipelines.insert(event.key) {
                         eprintln!(
@@ -147,23 +942,49 @@ impl SimulationState {
                             event.key
                         );
                     } else {
-                        self.storage_times.insert(
-                            event.key,
-                            self.latest_event.map(|e| e.timestamp).unwrap_or(0),
-                        );
+                        let storage_time = event.timestamp;
+
+                        self.storage_times.insert(event.key, storage_time);
+                        self.running_pipelines.insert(event.key);
+
+                        if self.job_level {
+                            let jobs = self.data_source.jobs_of_pipeline(event.key).await?;
+                            self.jobs.insert(
+                                event.key,
+                                jobs.iter()
+                                    .map(|(name, size)| StoredJob {
+                                        name: name.clone(),
+                                        size: *size,
+                                        is_stored: true,
+                                    })
+                                    .collect(),
+                            );
+                        }
+
+                        self.index_pipeline(event.key, size, storage_time).await?;
                     }
+                } else {
+                    // println!("Skipping pipeline due to unavailable size samples.");
                 }
             }
             SimulationEventKind::PipelineFinished => {
                 if let Ok(size) = self.data_source.size_of_pipeline(event.key).await {
                     self.occupied_storage = self.occupied_storage + size;
                 }
+
+                if self.running_pipelines.remove(&event.key)
+                    && self.protected_pipelines.contains(&event.key)
+                {
+                    self.protected_storage += self.stored_size(event.key).await?;
+                }
             }
         }
 
         self.latest_event = Some(event);
+        self.release_expired_protection().await?;
 
-        Ok(())
+        // Give retention policies the chance to react to the event, independent of the storage limit
+        self.expire().await
     }
 
     pub fn is_over_limit(&self) -> bool {
@@ -196,3 +1017,149 @@ impl SimulationState {
         Ok(())
     }
 }
+
+/// Removes the value from the group and drops the group once it is empty
+fn remove_from_group<K, V>(groups: &mut HashMap<K, BTreeSet<V>>, key: &K, value: &V)
+where
+    K: Eq + Hash,
+    V: Ord,
+{
+    if let Some(group) = groups.get_mut(key) {
+        group.remove(value);
+
+        if group.is_empty() {
+            groups.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytesize::ByteSize;
+    use futures::TryStreamExt;
+    use std::sync::Arc;
+
+    use super::SimulationState;
+    use crate::{
+        config::AlgorithmChain,
+        implementation::{trace_source::MemoryTraceSource, DataSource},
+    };
+
+    /// Prepares the simulation of a JSONL trace with the algorithm chain
+    fn prepare(trace: &str, chain: &str, storage_limit: ByteSize) -> SimulationState {
+        let records = trace
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let data_source =
+            DataSource::new(Arc::new(MemoryTraceSource::from_records(records).unwrap()));
+        let chain = AlgorithmChain::from_definition(chain).unwrap();
+
+        let mut state = SimulationState::new(&data_source, chain.build(0), storage_limit);
+        state.set_cleanup_rules(chain.build_rules());
+        state
+    }
+
+    /// Processes all events of the trace and returns the final state
+    async fn replay(mut state: SimulationState) -> SimulationState {
+        let data_source = state.data_source.clone();
+        let mut events = data_source.events();
+        while let Some(event) = events.try_next().await.unwrap() {
+            state.process(event).await.unwrap();
+            state.cleanup().await.unwrap();
+        }
+
+        state
+    }
+
+    #[async_std::test]
+    async fn keep_last_waits_for_running_pipelines() {
+        let state = replay(prepare(
+            r#"
+            {"timestamp": 1000, "kind": "created", "pipeline": 1, "size": 100, "ref": "main", "status": "success"}
+            {"timestamp": 2000, "kind": "created", "pipeline": 2, "size": 200, "ref": "main", "status": "success"}
+            {"timestamp": 3000, "kind": "finished", "pipeline": 1}
+            {"timestamp": 4000, "kind": "finished", "pipeline": 2}
+            {"timestamp": 5000, "kind": "created", "pipeline": 3, "size": 300, "ref": "main", "status": "success"}
+            {"timestamp": 6000, "kind": "finished", "pipeline": 3}
+            "#,
+            "KEEP.1-FIFO",
+            ByteSize::gb(1),
+        ))
+        .await;
+
+        assert_eq!(state.retention.expired_count, 2);
+        assert_eq!(state.retention.expired_bytes, ByteSize::b(300));
+        assert_eq!(state.occupied_storage, ByteSize::b(300));
+    }
+
+    #[async_std::test]
+    async fn ttl_counts_from_the_creation_of_the_first_pipeline() {
+        let day = 60 * 60 * 24;
+        let state = replay(prepare(
+            &format!(
+                r#"
+                {{"timestamp": {}, "kind": "created", "pipeline": 1, "size": 100, "ref": "main", "status": "success"}}
+                {{"timestamp": {}, "kind": "finished", "pipeline": 1}}
+                {{"timestamp": {}, "kind": "access", "pipeline": 1}}
+                {{"timestamp": {}, "kind": "created", "pipeline": 2, "size": 200, "ref": "main", "status": "success"}}
+                "#,
+                1_600_000_000,
+                1_600_000_000 + 60 * 60,
+                1_600_000_000 + 2 * 60 * 60,
+                1_600_000_000 + 2 * day,
+            ),
+            "TTL.1d-FIFO",
+            ByteSize::gb(1),
+        ))
+        .await;
+
+        assert_eq!(state.access_count_missed, 0);
+        assert_eq!(state.retention.expired_count, 1);
+        assert_eq!(state.retention.unused_bytes, ByteSize::b(0));
+        assert_eq!(state.occupied_storage, ByteSize::b(0));
+    }
+
+    #[async_std::test]
+    async fn eviction_trace_allows_unknown_status() {
+        let mut state = prepare(
+            r#"
+            {"timestamp": 1000, "kind": "created", "pipeline": 1, "size": 100}
+            {"timestamp": 2000, "kind": "finished", "pipeline": 1}
+            {"timestamp": 3000, "kind": "created", "pipeline": 2, "size": 100}
+            {"timestamp": 4000, "kind": "finished", "pipeline": 2}
+            "#,
+            "FIFO",
+            ByteSize::b(150),
+        );
+        state.enable_eviction_trace();
+
+        let state = replay(state).await;
+
+        assert_eq!(state.deleted_count, 1);
+        assert!(state.eviction_trace.is_some());
+    }
+
+    #[async_std::test]
+    async fn merges_apply_to_pipelines_without_status() {
+        let state = replay(prepare(
+            r#"
+            {"timestamp": 1000, "kind": "created", "pipeline": 1, "size": 100, "ref": "feature"}
+            {"timestamp": 2000, "kind": "finished", "pipeline": 1}
+            {"timestamp": 3000, "kind": "created", "pipeline": 2, "size": 100, "ref": "main"}
+            {"timestamp": 4000, "kind": "finished", "pipeline": 2}
+            {"timestamp": 5000, "kind": "merge", "ref": "feature"}
+            {"timestamp": 6000, "kind": "created", "pipeline": 3, "size": 100, "ref": "main"}
+            {"timestamp": 7000, "kind": "finished", "pipeline": 3}
+            "#,
+            "MERGED-FIFO",
+            ByteSize::b(250),
+        ))
+        .await;
+
+        assert_eq!(state.evictions_by_member[0].count, 1);
+        assert_eq!(state.evictions_by_member[1].count, 0);
+        assert!(!state.stored_pipelines.contains(&1));
+    }
+}
@@ -1,6 +1,6 @@
 use std::convert::TryInto;
 
-use super::data_source::DataSource;
+use super::{data_source::DataSource, RefClassifier, SizeStrategy};
 use anyhow::Result;
 use async_std::{
     fs::File,
@@ -17,23 +17,26 @@ struct MLDataPoint {
     merge_after: i64,
     access_count: usize,
     no_longer_needed_after: i64,
+    /// Category of the ref as numeric ref type, see `RefClassifier::ref_type`
+    ref_type: usize,
 }
 
 impl MLDataPoint {
     fn csv_header() -> &'static str {
-        "status,size,duration,merge_after,access_count,no_longer_needed_after\n"
+        "status,size,duration,merge_after,access_count,no_longer_needed_after,ref_type\n"
     }
 
     fn serialize(&self) -> String {
         // Note that we output the size as MB instead of Bytes since CreateML apparently uses 32-Bit numbers ...
         format!(
-            "{},{},{},{},{},{}\n",
+            "{},{},{},{},{},{},{}\n",
             self.status,
             self.size.as_u64() / 1024 / 1024,
             self.duration,
             self.merge_after,
             self.access_count,
             self.no_longer_needed_after,
+            self.ref_type,
         )
     }
 }
@@ -44,8 +47,15 @@ pub struct StaticMLGenerator {
 }
 
 impl StaticMLGenerator {
-    pub async fn new(database: &str, seed: u64) -> Result<Self> {
-        let data_source = DataSource::open(database, seed).await?;
+    pub async fn new(
+        database: &str,
+        seed: u64,
+        size_strategy: SizeStrategy,
+        ref_classifier: RefClassifier,
+    ) -> Result<Self> {
+        let mut data_source = DataSource::open(database, seed, size_strategy).await?;
+        data_source.set_ref_classifier(ref_classifier);
+
         let event_count = data_source
             .all_pipelines()
             .try_collect::<Vec<_>>()
@@ -80,6 +90,8 @@ impl StaticMLGenerator {
                 .accesses_after_timestamp(pipeline.id, pipeline.created_at)
                 .await?;
 
+            let pipeline_ref = self.data_source.ref_of_pipeline(pipeline.id).await?;
+
             let data_point = MLDataPoint {
                 status: pipeline.raw_status,
                 size: self.data_source.size_of_pipeline(pipeline.id).await?,
@@ -87,6 +99,10 @@ impl StaticMLGenerator {
                 merge_after: 0,
                 access_count: accesses.len(),
                 no_longer_needed_after: accesses.first().map(|i| *i).unwrap_or(0),
+                ref_type: self
+                    .data_source
+                    .ref_classifier()
+                    .ref_type(pipeline_ref.as_deref()),
             };
 
             f.write(data_point.serialize().as_bytes()).await?;
@@ -1,10 +1,13 @@
 use super::{
     data_source::{SimulationEvent, SimulationEventKind},
-    state::SimulationState,
+    eviction_trace::EvictionTrace,
+    state::{MemberEvictions, RetentionStatistics, SimulationState},
+    tenants::MultiTenantState,
 };
 use anyhow::Result;
 use bytesize::ByteSize;
-use std::{fmt, fs::create_dir_all, fs::File, io::Write, path::PathBuf};
+use serde::{Serialize, Serializer};
+use std::{fmt, fs::create_dir_all, fs::File, io::Write, path::PathBuf, time::Duration};
 
 pub struct DataPoint {
     event: SimulationEvent,
@@ -16,6 +19,18 @@ pub struct DataPoint {
     access_count_missed: u32,
 
     deleted_count: u32,
+
+    missed_bytes: ByteSize,
+    redownload_bytes: ByteSize,
+    rerun_duration: i64,
+
+    retention: RetentionStatistics,
+
+    protected_storage: ByteSize,
+    protected_access_count: u32,
+
+    cleanup_runs: u32,
+    over_limit_duration: i64,
 }
 
 impl DataPoint {
@@ -27,9 +42,52 @@ impl DataPoint {
             access_count: state.access_count,
             access_count_missed: state.access_count_missed,
             deleted_count: state.deleted_count,
+            missed_bytes: state.missed_bytes,
+            redownload_bytes: state.redownload_bytes,
+            rerun_duration: state.rerun_duration,
+            retention: state.retention,
+            protected_storage: state.protected_storage,
+            protected_access_count: state.protected_access_count,
+            cleanup_runs: state.cleanup_runs,
+            over_limit_duration: state.over_limit_duration,
         }
     }
 
+    /// Sums up the states of all tenants, the over-limit duration refers to the storage limit shared by all tenants
+    pub fn for_tenants(state: &MultiTenantState) -> Self {
+        let mut data_point = Self::new(&state.states[0]);
+
+        for tenant in state.states[1..].iter() {
+            data_point.add(&Self::new(tenant));
+        }
+        data_point.over_limit_duration = state.over_limit_duration;
+
+        data_point
+    }
+
+    fn add(&mut self, other: &DataPoint) {
+        self.occupied_storage += other.occupied_storage;
+        self.stored_pipeline_count += other.stored_pipeline_count;
+        self.access_count += other.access_count;
+        self.access_count_missed += other.access_count_missed;
+        self.deleted_count += other.deleted_count;
+        self.missed_bytes += other.missed_bytes;
+        self.redownload_bytes += other.redownload_bytes;
+        self.rerun_duration += other.rerun_duration;
+
+        let (retention, other_retention) = (&mut self.retention, &other.retention);
+        retention.expired_count += other_retention.expired_count;
+        retention.expired_bytes += other_retention.expired_bytes;
+        retention.unused_bytes += other_retention.unused_bytes;
+        retention.missed_access_count += other_retention.missed_access_count;
+        retention.redownload_bytes += other_retention.redownload_bytes;
+
+        self.protected_storage += other.protected_storage;
+        self.protected_access_count += other.protected_access_count;
+        self.cleanup_runs += other.cleanup_runs;
+        self.over_limit_duration += other.over_limit_duration;
+    }
+
     pub fn missed_percentage(&self) -> f64 {
         let missed: f64 = self.access_count_missed.into();
         let total: f64 = self.access_count.into();
@@ -42,7 +100,7 @@ impl DataPoint {
     }
 
     pub fn csv_header() -> &'static str {
-        "Occupied storage,Stored count,Deleted count,Access count,Missed access count,Missed fraction"
+        "Occupied storage,Stored count,Deleted count,Access count,Missed access count,Missed fraction,Missed bytes,Re-download bytes,Re-run duration,Expired count,Expired bytes,Protected storage"
     }
 }
 
@@ -50,30 +108,229 @@ impl fmt::Display for DataPoint {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{},{},{},{},{},{}",
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
             self.occupied_storage.as_u64(),
             self.stored_pipeline_count,
             self.deleted_count,
             self.access_count,
             self.access_count_missed,
-            self.missed_percentage()
+            self.missed_percentage(),
+            self.missed_bytes.as_u64(),
+            self.redownload_bytes.as_u64(),
+            self.rerun_duration,
+            self.retention.expired_count,
+            self.retention.expired_bytes.as_u64(),
+            self.protected_storage.as_u64()
         )
     }
 }
 
+fn serialize_byte_size<S: Serializer>(size: &ByteSize, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(size.as_u64())
+}
+
+/// Evictions attributed to a single member of the algorithm chain
+#[derive(Serialize)]
+pub struct MemberSummary {
+    pub name: String,
+    pub evictions: u32,
+    #[serde(serialize_with = "serialize_byte_size")]
+    pub evicted_bytes: ByteSize,
+}
+
+/// Key figures of a single tenant of a multi-tenant simulation run
+#[derive(Serialize)]
+pub struct TenantSummary {
+    pub name: String,
+    /// Storage reserved for the tenant, excluding the shared pool
+    #[serde(serialize_with = "serialize_byte_size")]
+    pub quota: ByteSize,
+    #[serde(flatten)]
+    pub summary: Summary,
+}
+
+/// Key figures of a finished simulation run
+#[derive(Serialize)]
+pub struct Summary {
+    pub miss_fraction: f64,
+    pub access_count: u32,
+    pub missed_access_count: u32,
+    pub deleted_count: u32,
+    /// Occupied storage averaged over the simulated time span
+    #[serde(serialize_with = "serialize_byte_size")]
+    pub mean_occupied_storage: ByteSize,
+    #[serde(serialize_with = "serialize_byte_size")]
+    pub peak_occupied_storage: ByteSize,
+    /// Number of evictions selected by the fallback algorithm
+    pub fallback_count: u32,
+    /// Total number of evictions selected by the algorithm chain
+    pub decision_count: u32,
+    /// Number of pipelines removed by retention policies
+    pub expired_count: u32,
+    #[serde(serialize_with = "serialize_byte_size")]
+    pub expired_bytes: ByteSize,
+    /// Size of expired pipelines which have not been accessed while they were stored (wasted storage)
+    #[serde(serialize_with = "serialize_byte_size")]
+    pub expired_unused_bytes: ByteSize,
+    /// Accesses missed because the pipeline had been expired by a retention policy
+    pub expired_missed_access_count: u32,
+    #[serde(serialize_with = "serialize_byte_size")]
+    pub expired_redownload_bytes: ByteSize,
+    /// Storage occupied by protected pipelines averaged over the simulated time span (cost of the protection rules)
+    #[serde(serialize_with = "serialize_byte_size")]
+    pub mean_protected_storage: ByteSize,
+    #[serde(serialize_with = "serialize_byte_size")]
+    pub peak_protected_storage: ByteSize,
+    /// Accesses to pipelines which were protected at the time of the access.
+    /// This is an upper bound of what the protection rules gain, as some of them would have been stored anyway.
+    pub protected_access_count: u32,
+    /// Number of cleanup runs which evicted at least one pipeline
+    pub cleanup_runs: u32,
+    /// Simulated time in seconds during which the occupied storage exceeded the storage limit
+    pub over_limit_seconds: i64,
+    pub wall_clock_seconds: f64,
+    /// Evictions per chain member, only included in the JSON output since the chain length varies
+    #[serde(rename = "chain")]
+    pub members: Vec<MemberSummary>,
+    /// Key figures per tenant, only present for multi-tenant simulations
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tenants: Vec<TenantSummary>,
+}
+
+impl Summary {
+    pub fn csv_header() -> &'static str {
+        "Missed fraction,Access count,Missed access count,Deleted count,Mean occupied storage,Peak occupied storage,Fallback count,Decision count,Expired count,Expired bytes,Expired unused bytes,Expired missed access count,Expired re-download bytes,Mean protected storage,Peak protected storage,Protected access count,Cleanup runs,Over limit seconds,Wall clock seconds"
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.miss_fraction,
+            self.access_count,
+            self.missed_access_count,
+            self.deleted_count,
+            self.mean_occupied_storage.as_u64(),
+            self.peak_occupied_storage.as_u64(),
+            self.fallback_count,
+            self.decision_count,
+            self.expired_count,
+            self.expired_bytes.as_u64(),
+            self.expired_unused_bytes.as_u64(),
+            self.expired_missed_access_count,
+            self.expired_redownload_bytes.as_u64(),
+            self.mean_protected_storage.as_u64(),
+            self.peak_protected_storage.as_u64(),
+            self.protected_access_count,
+            self.cleanup_runs,
+            self.over_limit_seconds,
+            self.wall_clock_seconds
+        )
+    }
+}
+
+/// Statistics of a single tenant of a multi-tenant simulation
+struct TenantStatistics {
+    name: String,
+    quota: ByteSize,
+    statistics: Statistics,
+}
+
 pub struct Statistics {
     data_points: Vec<DataPoint>,
+
+    /// Names of the algorithm chain members in the order of `member_evictions`
+    member_names: Vec<String>,
+    /// Evictions per chain member alongside the index of the first data point they apply to.
+    /// Only recorded when they change as most events do not lead to any evictions.
+    member_evictions: Vec<(usize, Vec<MemberEvictions>)>,
+    wall_clock_time: Duration,
+    eviction_trace: Option<EvictionTrace>,
+
+    /// Per-tenant statistics in the order of `MultiTenantState::states`, empty unless simulating tenants
+    tenants: Vec<TenantStatistics>,
 }
 
 impl Statistics {
     pub fn new() -> Self {
         Self {
             data_points: Vec::new(),
+            member_names: Vec::new(),
+            member_evictions: Vec::new(),
+            wall_clock_time: Duration::default(),
+            eviction_trace: None,
+            tenants: Vec::new(),
         }
     }
 
+    pub fn set_member_names(&mut self, member_names: Vec<String>) {
+        self.member_names = member_names;
+    }
+
     pub fn record(&mut self, state: &SimulationState) {
         self.data_points.push(DataPoint::new(state));
+        self.record_member_evictions(&state.evictions_by_member);
+    }
+
+    /// Records the evictions per member for the latest data point if they changed, returns whether they did
+    fn record_member_evictions(&mut self, evictions: &[MemberEvictions]) -> bool {
+        let is_unchanged = match self.member_evictions.last() {
+            Some((_, previous)) => previous.as_slice() == evictions,
+            None => evictions.iter().all(|e| e.count == 0),
+        };
+
+        if is_unchanged {
+            return false;
+        }
+
+        self.member_evictions
+            .push((self.data_points.len() - 1, evictions.to_vec()));
+        true
+    }
+
+    /// Tracks the tenants separately in addition to the totals, has to be called after `set_member_names`
+    pub fn set_tenants(&mut self, names: Vec<String>, quotas: Vec<ByteSize>) {
+        self.tenants = names
+            .into_iter()
+            .zip(quotas)
+            .map(|(name, quota)| {
+                let mut statistics = Statistics::new();
+                statistics.set_member_names(self.member_names.clone());
+
+                TenantStatistics {
+                    name,
+                    quota,
+                    statistics,
+                }
+            })
+            .collect();
+    }
+
+    pub fn record_tenants(&mut self, state: &MultiTenantState) {
+        self.data_points.push(DataPoint::for_tenants(state));
+
+        let mut is_changed = false;
+        for (tenant, state) in self.tenants.iter_mut().zip(state.states.iter()) {
+            tenant.statistics.data_points.push(DataPoint::new(state));
+            is_changed |= tenant
+                .statistics
+                .record_member_evictions(&state.evictions_by_member);
+        }
+
+        if is_changed {
+            let mut totals = state.states[0].evictions_by_member.clone();
+            for tenant in state.states[1..].iter() {
+                for (evictions, other) in totals.iter_mut().zip(tenant.evictions_by_member.iter()) {
+                    evictions.count += other.count;
+                    evictions.bytes += other.bytes;
+                }
+            }
+
+            self.member_evictions
+                .push((self.data_points.len() - 1, totals));
+        }
     }
 
     pub fn current_miss_percentage(&self) -> f64 {
@@ -84,6 +341,133 @@ impl Statistics {
         }
     }
 
+    pub fn record_completion(
+        &mut self,
+        wall_clock_time: Duration,
+        eviction_trace: Option<EvictionTrace>,
+    ) {
+        self.wall_clock_time = wall_clock_time;
+        self.eviction_trace = eviction_trace;
+    }
+
+    /// Average of a storage figure weighted by the time span until the next data point
+    fn time_weighted_mean(&self, storage: impl Fn(&DataPoint) -> ByteSize) -> ByteSize {
+        let (first, last) = match (self.data_points.first(), self.data_points.last()) {
+            (Some(first), Some(last)) => (first.event.timestamp, last.event.timestamp),
+            _ => return ByteSize::b(0),
+        };
+
+        if last <= first {
+            return ByteSize::b(0);
+        }
+
+        let weighted_sum: f64 = self
+            .data_points
+            .windows(2)
+            .map(|w| {
+                let duration = (w[1].event.timestamp - w[0].event.timestamp) as f64;
+                storage(&w[0]).as_u64() as f64 * duration
+            })
+            .sum();
+
+        ByteSize::b((weighted_sum / (last - first) as f64) as u64)
+    }
+
+    fn peak(&self, storage: impl Fn(&DataPoint) -> ByteSize) -> ByteSize {
+        self.data_points
+            .iter()
+            .map(storage)
+            .max()
+            .unwrap_or(ByteSize::b(0))
+    }
+
+    pub fn summary(&self) -> Summary {
+        let last = self.data_points.last();
+        let member_evictions = self.member_evictions.last().map(|(_, e)| e);
+
+        let members = self
+            .member_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let evictions = member_evictions.and_then(|e| e.get(i));
+
+                MemberSummary {
+                    name: name.clone(),
+                    evictions: evictions.map_or(0, |e| e.count),
+                    evicted_bytes: evictions.map_or(ByteSize::b(0), |e| e.bytes),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let retention = last.map_or_else(RetentionStatistics::default, |d| d.retention);
+
+        Summary {
+            miss_fraction: self.current_miss_percentage(),
+            access_count: last.map_or(0, |d| d.access_count),
+            missed_access_count: last.map_or(0, |d| d.access_count_missed),
+            deleted_count: last.map_or(0, |d| d.deleted_count),
+            mean_occupied_storage: self.time_weighted_mean(|d| d.occupied_storage),
+            peak_occupied_storage: self.peak(|d| d.occupied_storage),
+            fallback_count: members.last().map_or(0, |m| m.evictions),
+            decision_count: members.iter().map(|m| m.evictions).sum(),
+            expired_count: retention.expired_count,
+            expired_bytes: retention.expired_bytes,
+            expired_unused_bytes: retention.unused_bytes,
+            expired_missed_access_count: retention.missed_access_count,
+            expired_redownload_bytes: retention.redownload_bytes,
+            mean_protected_storage: self.time_weighted_mean(|d| d.protected_storage),
+            peak_protected_storage: self.peak(|d| d.protected_storage),
+            protected_access_count: last.map_or(0, |d| d.protected_access_count),
+            cleanup_runs: last.map_or(0, |d| d.cleanup_runs),
+            over_limit_seconds: last.map_or(0, |d| d.over_limit_duration),
+            wall_clock_seconds: self.wall_clock_time.as_secs_f64(),
+            members,
+            tenants: self
+                .tenants
+                .iter()
+                .map(|tenant| TenantSummary {
+                    name: tenant.name.clone(),
+                    quota: tenant.quota,
+                    summary: tenant.statistics.summary(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes the eviction trace if it has been enabled for the simulation
+    pub fn write_eviction_trace(&self, path: PathBuf) -> Result<()> {
+        if let Some(trace) = self.eviction_trace.as_ref() {
+            trace.write_csv(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes one summary row per tenant if the simulation has been split into tenants
+    pub fn write_tenant_csv(&self, path: PathBuf) -> Result<()> {
+        if self.tenants.is_empty() {
+            return Ok(());
+        }
+
+        create_dir_all(path.parent().unwrap())?;
+        let mut f = File::create(path)?;
+
+        writeln!(f, "Tenant,Quota,{}", Summary::csv_header())?;
+
+        for tenant in self.tenants.iter() {
+            writeln!(
+                f,
+                "{},{},{}",
+                tenant.name,
+                tenant.quota.as_u64(),
+                tenant.statistics.summary()
+            )?;
+        }
+
+        Ok(())
+    }
+
     pub fn write_csv(&self, path: PathBuf) -> Result<()> {
         create_dir_all(path.parent().unwrap())?;
         let mut f = File::create(path)?;
@@ -95,14 +479,34 @@ impl Statistics {
 
 impl fmt::Display for Statistics {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{}", DataPoint::csv_header())?;
+        write!(f, "{}", DataPoint::csv_header())?;
+        for name in self.member_names.iter() {
+            write!(f, ",{} evictions,{} evicted bytes", name, name)?;
+        }
+        writeln!(f)?;
 
-        for data_point in self
-            .data_points
-            .iter()
-            .filter(|e| e.event.kind == SimulationEventKind::PipelineFinished)
-        {
-            writeln!(f, "{}", data_point)?;
+        let mut member_evictions = self.member_evictions.iter().peekable();
+        let mut current_evictions = None;
+
+        for (i, data_point) in self.data_points.iter().enumerate() {
+            while let Some((_, evictions)) = member_evictions.next_if(|(first, _)| *first <= i) {
+                current_evictions = Some(evictions);
+            }
+
+            if data_point.event.kind != SimulationEventKind::PipelineFinished {
+                continue;
+            }
+
+            write!(f, "{}", data_point)?;
+            for member in 0..self.member_names.len() {
+                match current_evictions.and_then(|e| e.get(member)) {
+                    Some(evictions) => {
+                        write!(f, ",{},{}", evictions.count, evictions.bytes.as_u64())?
+                    }
+                    None => write!(f, ",0,0")?,
+                }
+            }
+            writeln!(f)?;
         }
 
         Ok(())
@@ -0,0 +1,293 @@
+use anyhow::{bail, Result};
+use bytesize::ByteSize;
+use regex::Regex;
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use super::{
+    data_source::{DataSource, SimulationEvent, SimulationEventKind},
+    deserialize::deserialize_regex,
+    eviction_trace::EvictionTrace,
+    state::SimulationState,
+    CleanupAlgorithm, CleanupMode, CleanupRules, PipelineID,
+};
+
+/// Name of the implicit tenant owning all pipelines which are not matched by any configured tenant
+const UNASSIGNED_TENANT: &str = "unassigned";
+
+/// Property of a pipeline which determines the tenant it belongs to
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TenantKey {
+    /// Repository through which the artifacts of the pipeline are accessed
+    Repository,
+    /// Ref (branch or tag) the pipeline ran on, e.g. to give `release/` branches their own quota
+    Ref,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Tenant {
+    pub name: String,
+    /// Regular expression matched against the repository or ref of a pipeline
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub pattern: Regex,
+    /// Storage reserved for the tenant in GB
+    pub quota: u64,
+}
+
+/// Splits the storage between tenants, each of which is guaranteed its quota. Storage not reserved by
+/// any quota forms a shared pool which tenants may use once they exceed their own quota. Pipelines
+/// not matched by any tenant belong to an implicit `unassigned` tenant which can only use the shared pool.
+///
+/// ```toml
+/// key = "repository"
+///
+/// [[tenant]]
+/// name = "frontend"
+/// pattern = "^frontend/"
+/// quota = 128
+///
+/// [[tenant]]
+/// name = "backend"
+/// pattern = "^(backend|services)/"
+/// quota = 256
+/// ```
+#[derive(Deserialize, Clone, Debug)]
+pub struct TenantConfig {
+    pub key: TenantKey,
+    #[serde(rename = "tenant", default)]
+    pub tenants: Vec<Tenant>,
+}
+
+impl TenantConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let config: Self = toml::from_str(&fs::read_to_string(path)?)?;
+
+        if config.tenants.is_empty() {
+            bail!("Tenant file {} does not define any tenants", path.display());
+        }
+
+        if config.tenants.iter().any(|t| t.name == UNASSIGNED_TENANT) {
+            bail!(
+                "The tenant name '{}' is reserved for pipelines not matched by any tenant",
+                UNASSIGNED_TENANT
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// Number of tenants including the implicit tenant of unmatched pipelines
+    pub fn tenant_count(&self) -> usize {
+        self.tenants.len() + 1
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.tenants
+            .iter()
+            .map(|t| t.name.clone())
+            .chain(std::iter::once(UNASSIGNED_TENANT.to_owned()))
+            .collect()
+    }
+
+    pub fn quotas(&self) -> Vec<ByteSize> {
+        self.tenants
+            .iter()
+            .map(|t| ByteSize::gb(t.quota))
+            .chain(std::iter::once(ByteSize::b(0)))
+            .collect()
+    }
+
+    /// Index of the first tenant matching the repository or ref, unmatched values belong to the implicit last tenant
+    fn tenant_of(&self, value: Option<&str>) -> usize {
+        value
+            .and_then(|value| self.tenants.iter().position(|t| t.pattern.is_match(value)))
+            .unwrap_or(self.tenants.len())
+    }
+}
+
+/// Simulates every tenant with its own state and algorithm instance. Events concerning a pipeline are
+/// only processed by the state of the tenant owning it while all other states merely advance their clock.
+pub struct MultiTenantState {
+    data_source: DataSource,
+    config: Arc<TenantConfig>,
+
+    pub states: Vec<SimulationState>,
+    pub quotas: Vec<ByteSize>,
+    pub storage_limit: ByteSize,
+    /// Storage not reserved by any quota
+    shared_pool: ByteSize,
+
+    /// Simulated time in seconds during which the storage occupied by all tenants exceeded the storage limit
+    pub over_limit_duration: i64,
+    latest_timestamp: Option<i64>,
+
+    /// Tenant of each pipeline encountered so far
+    assignments: HashMap<PipelineID, usize>,
+}
+
+impl MultiTenantState {
+    /// Creates a state for each tenant, `algorithms` has to contain one algorithm and its rules per tenant
+    pub fn new(
+        data_source: &DataSource,
+        config: Arc<TenantConfig>,
+        algorithms: Vec<(Box<dyn CleanupAlgorithm>, CleanupRules)>,
+        storage_limit: ByteSize,
+    ) -> Result<Self> {
+        let quotas = config.quotas();
+        let reserved = quotas
+            .iter()
+            .fold(ByteSize::b(0), |sum, quota| sum + *quota);
+
+        if reserved > storage_limit {
+            bail!(
+                "The tenant quotas ({}) exceed the storage limit ({})",
+                reserved,
+                storage_limit
+            );
+        }
+
+        let shared_pool = ByteSize::b(storage_limit.as_u64() - reserved.as_u64());
+        let states = algorithms
+            .into_iter()
+            .zip(quotas.iter())
+            .map(|((algorithm, rules), quota)| {
+                let mut state = SimulationState::new(data_source, algorithm, *quota + shared_pool);
+                state.set_cleanup_rules(rules);
+                state
+            })
+            .collect();
+
+        Ok(Self {
+            data_source: data_source.clone(),
+            config,
+            states,
+            quotas,
+            storage_limit,
+            shared_pool,
+            over_limit_duration: 0,
+            latest_timestamp: None,
+            assignments: HashMap::new(),
+        })
+    }
+
+    pub fn enable_eviction_trace(&mut self) {
+        for state in self.states.iter_mut() {
+            state.enable_eviction_trace();
+        }
+    }
+
+    /// Combines the eviction traces of all tenants
+    pub fn take_eviction_trace(&mut self) -> Option<EvictionTrace> {
+        let traces = self
+            .states
+            .iter_mut()
+            .filter_map(|state| state.take_eviction_trace())
+            .collect::<Vec<_>>();
+
+        if traces.is_empty() {
+            None
+        } else {
+            Some(EvictionTrace::merge(traces))
+        }
+    }
+
+    pub fn enable_job_level(&mut self) {
+        for state in self.states.iter_mut() {
+            state.enable_job_level();
+        }
+    }
+
+    pub fn set_cleanup_mode(&mut self, cleanup_mode: CleanupMode) {
+        for state in self.states.iter_mut() {
+            state.set_cleanup_mode(cleanup_mode);
+        }
+    }
+
+    pub fn occupied_storage(&self) -> ByteSize {
+        self.states
+            .iter()
+            .fold(ByteSize::b(0), |sum, state| sum + state.occupied_storage)
+    }
+
+    async fn tenant_of_pipeline(&mut self, id: PipelineID) -> Result<usize> {
+        if let Some(tenant) = self.assignments.get(&id) {
+            return Ok(*tenant);
+        }
+
+        let value = match self.config.key {
+            TenantKey::Repository => self.data_source.repository_of_pipeline(id).await?,
+            TenantKey::Ref => self.data_source.ref_of_pipeline(id).await?,
+        };
+        let tenant = self.config.tenant_of(value.as_deref());
+
+        self.assignments.insert(id, tenant);
+
+        Ok(tenant)
+    }
+
+    /// Tenant responsible for processing the event, `None` if it concerns all tenants
+    async fn tenant_of_event(&mut self, event: &SimulationEvent) -> Result<Option<usize>> {
+        match event.kind {
+            SimulationEventKind::PipelineCreated | SimulationEventKind::PipelineFinished => {
+                Ok(Some(self.tenant_of_pipeline(event.key).await?))
+            }
+            SimulationEventKind::Access => match self.data_source.access_log_entry(event.key).await
+            {
+                Ok(entry) => Ok(Some(self.tenant_of_pipeline(entry.pipeline).await?)),
+                // Let a single state report the broken entry
+                Err(_) => Ok(Some(self.config.tenants.len())),
+            },
+            SimulationEventKind::MergeRequestEvent => Ok(None),
+        }
+    }
+
+    pub async fn process(&mut self, event: SimulationEvent) -> Result<()> {
+        if let Some(latest_timestamp) = self.latest_timestamp {
+            if self.occupied_storage() > self.storage_limit {
+                self.over_limit_duration += event.timestamp - latest_timestamp;
+            }
+        }
+        self.latest_timestamp = Some(event.timestamp);
+
+        let tenant = self.tenant_of_event(&event).await?;
+
+        for (i, state) in self.states.iter_mut().enumerate() {
+            match tenant {
+                Some(tenant) if tenant != i => state.advance(event).await?,
+                _ => state.process(event).await?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Cleans up each tenant using its quota plus whatever part of the shared pool is not occupied by the
+    /// other tenants. The shared pool is handed out first come, first served: tenants are never forced to
+    /// give up shared storage they already occupy.
+    pub async fn cleanup(&mut self) -> Result<()> {
+        for i in 0..self.states.len() {
+            let borrowed: u64 = self
+                .states
+                .iter()
+                .zip(self.quotas.iter())
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (state, quota))| {
+                    state
+                        .occupied_storage
+                        .as_u64()
+                        .saturating_sub(quota.as_u64())
+                })
+                .sum();
+
+            let available = self.shared_pool.as_u64().saturating_sub(borrowed);
+            let state = &mut self.states[i];
+
+            state.storage_limit = self.quotas[i] + ByteSize::b(available);
+            state.cleanup().await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,536 @@
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use super::{
+    super::{
+        data_source::{
+            AccessLogEntry, MergeRequestEvent, Pipeline, PipelineStatus, SimulationEvent,
+            SimulationEventKind,
+        },
+        AccessLogEntryID, MergeRequestEventID, PipelineID,
+    },
+    SqliteTraceSource, TraceSource,
+};
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceRecordKind {
+    /// A pipeline has been started, its artifacts are stored from now on
+    Created,
+    /// A pipeline has finished, its artifacts occupy storage from now on
+    Finished,
+    /// Artifacts of a pipeline have been accessed
+    Access,
+    /// The branch given as the ref has been merged
+    Merge,
+}
+
+/// Single line of a flat trace file.
+/// Size, ref and status may be given on any record of a pipeline, later records override earlier ones.
+#[derive(Deserialize, Clone, Debug)]
+pub struct TraceRecord {
+    pub timestamp: i64,
+    pub kind: TraceRecordKind,
+    pub pipeline: Option<PipelineID>,
+    /// Size of the pipeline in bytes
+    pub size: Option<i64>,
+    #[serde(rename = "ref")]
+    pub pipeline_ref: Option<String>,
+    pub status: Option<String>,
+    #[serde(default)]
+    pub repository: Option<String>,
+}
+
+impl TraceRecord {
+    /// Parses a CSV line given the column names of the header
+    fn from_csv(header: &[&str], line: &str) -> Result<Self> {
+        let mut fields = HashMap::new();
+
+        for (name, value) in header.iter().zip(line.split(',')) {
+            let value = value.trim();
+
+            if !value.is_empty() {
+                fields.insert(*name, value);
+            }
+        }
+
+        let field = |name: &str| fields.get(name).copied();
+
+        Ok(Self {
+            timestamp: field("timestamp")
+                .ok_or_else(|| anyhow!("Missing timestamp"))?
+                .parse()?,
+            kind: serde_json::from_value(serde_json::Value::String(
+                field("kind")
+                    .ok_or_else(|| anyhow!("Missing kind"))?
+                    .to_owned(),
+            ))?,
+            pipeline: field("pipeline").map(|p| p.parse()).transpose()?,
+            size: field("size").map(|s| s.parse()).transpose()?,
+            pipeline_ref: field("ref").map(|r| r.to_owned()),
+            status: field("status").map(|s| s.to_owned()),
+            repository: field("repository").map(|r| r.to_owned()),
+        })
+    }
+}
+
+/// Id, status, duration, creation time, ref and jobs of a pipeline as preloaded from the database
+type PipelineRow = (
+    PipelineID,
+    Option<String>,
+    Option<i64>,
+    Option<i64>,
+    Option<String>,
+    Option<String>,
+);
+
+#[derive(Default)]
+struct MemoryPipeline {
+    created_at: Option<i64>,
+    finished_at: Option<i64>,
+    duration: Option<i64>,
+    size: Option<i64>,
+    jobs: Option<String>,
+    pipeline_ref: Option<String>,
+    status: Option<PipelineStatus>,
+    raw_status: Option<String>,
+    repository: Option<String>,
+}
+
+/// Trace which is held in memory and shared read-only between all simulations.
+/// It is either loaded from a flat JSONL or CSV file with one `TraceRecord` per line or preloaded from a database.
+/// Files with a `.csv` extension need a header naming the columns `timestamp,kind,pipeline,size,ref,status`
+/// (optionally followed by `repository`),
+/// fields may be left empty but can not be quoted.
+pub struct MemoryTraceSource {
+    events: Vec<SimulationEvent>,
+    pipelines: HashMap<PipelineID, MemoryPipeline>,
+    accesses: HashMap<AccessLogEntryID, AccessLogEntry>,
+    merges: HashMap<MergeRequestEventID, MergeRequestEvent>,
+    /// Timestamps of the relevant accesses to each pipeline in chronological order
+    access_times: HashMap<PipelineID, Vec<i64>>,
+    pipelines_by_ref: HashMap<String, Vec<PipelineID>>,
+    /// Source used to sample the size of pipelines without a known size
+    size_source: Option<SqliteTraceSource>,
+}
+
+impl MemoryTraceSource {
+    fn empty() -> Self {
+        Self {
+            events: Vec::new(),
+            pipelines: HashMap::new(),
+            accesses: HashMap::new(),
+            merges: HashMap::new(),
+            access_times: HashMap::new(),
+            pipelines_by_ref: HashMap::new(),
+            size_source: None,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut lines = reader.lines().enumerate();
+        let mut records = Vec::new();
+
+        if path.extension().is_some_and(|e| e == "csv") {
+            let header = match lines.next() {
+                Some((_, header)) => header?,
+                None => bail!("Trace file {} is empty", path.display()),
+            };
+            let columns = header.split(',').map(|c| c.trim()).collect::<Vec<_>>();
+
+            for (number, line) in lines {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    records.push(
+                        TraceRecord::from_csv(&columns, &line)
+                            .with_context(|| format!("Invalid record in line {}", number + 1))?,
+                    );
+                }
+            }
+        } else {
+            for (number, line) in lines {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    records.push(
+                        serde_json::from_str(&line)
+                            .with_context(|| format!("Invalid record in line {}", number + 1))?,
+                    );
+                }
+            }
+        }
+
+        Self::from_records(records)
+    }
+
+    pub fn from_records(mut records: Vec<TraceRecord>) -> Result<Self> {
+        records.sort_by_key(|r| r.timestamp);
+
+        let mut source = Self::empty();
+        source.events.reserve(records.len());
+
+        for record in records {
+            let index = source.events.len() as i64;
+
+            let (kind, key) = match record.kind {
+                TraceRecordKind::Merge => {
+                    let source_branch = record.pipeline_ref.clone().ok_or_else(|| {
+                        anyhow!("Merge record at {} has no ref", record.timestamp)
+                    })?;
+
+                    source.merges.insert(
+                        index,
+                        MergeRequestEvent {
+                            source_branch,
+                            status: "merged".to_owned(),
+                            action: "merge".to_owned(),
+                        },
+                    );
+
+                    (SimulationEventKind::MergeRequestEvent, index)
+                }
+                kind => {
+                    let id = record
+                        .pipeline
+                        .ok_or_else(|| anyhow!("Record at {} has no pipeline", record.timestamp))?;
+
+                    source.update_pipeline(id, &record)?;
+
+                    match kind {
+                        TraceRecordKind::Created => (SimulationEventKind::PipelineCreated, id),
+                        TraceRecordKind::Finished => (SimulationEventKind::PipelineFinished, id),
+                        _ => {
+                            let entry = AccessLogEntry {
+                                timestamp: record.timestamp,
+                                pipeline: id,
+                                job: None,
+                            };
+                            source.insert_access(index, entry, true);
+
+                            (SimulationEventKind::Access, index)
+                        }
+                    }
+                }
+            };
+
+            source.push_event(record.timestamp, kind, key);
+        }
+
+        for pipeline in source.pipelines.values_mut() {
+            pipeline.jobs = Some(String::new());
+            pipeline.duration = match (pipeline.created_at, pipeline.finished_at) {
+                (Some(created_at), Some(finished_at)) => Some(finished_at - created_at),
+                _ => Some(0),
+            };
+        }
+
+        source.index_refs();
+
+        Ok(source)
+    }
+
+    /// Loads all events and metadata of a database into memory.
+    /// Pipeline sizes are still sampled by the database source when they are first requested.
+    pub async fn preload(database: SqliteTraceSource) -> Result<Self> {
+        let con = database.connection();
+        let mut source = Self::empty();
+
+        source.events = sqlx::query_as("SELECT * FROM SimulationEvent ORDER BY timestamp")
+            .fetch_all(con)
+            .await?;
+
+        let pipelines: Vec<PipelineRow> =
+            sqlx::query_as("SELECT id,status,duration,createdAt,ref,jobs FROM Pipeline")
+                .fetch_all(con)
+                .await?;
+
+        for (id, raw_status, duration, created_at, pipeline_ref, jobs) in pipelines {
+            source.pipelines.insert(
+                id,
+                MemoryPipeline {
+                    created_at,
+                    finished_at: None,
+                    duration,
+                    size: None,
+                    jobs,
+                    pipeline_ref,
+                    // Unknown statuses are treated like missing ones instead of failing the whole preload
+                    status: raw_status.as_ref().and_then(|s| s.parse().ok()),
+                    raw_status,
+                    repository: None,
+                },
+            );
+        }
+
+        type AccessRow = (
+            AccessLogEntryID,
+            i64,
+            Option<PipelineID>,
+            bool,
+            Option<String>,
+            Option<String>,
+        );
+        let accesses: Vec<AccessRow> = sqlx::query_as(
+            "SELECT id,timestamp,pipeline,NOT isIrrelevant AND NOT isAutomatic,repository,job FROM AccessLog ORDER BY id",
+        )
+        .fetch_all(con)
+        .await?;
+
+        for (id, timestamp, pipeline, is_relevant, repository, job) in accesses {
+            // Entries without a pipeline can not be looked up, just like in the database
+            if let Some(pipeline) = pipeline {
+                // The repository of the first access determines the repository of the pipeline
+                if let (Some(memory_pipeline), Some(repository)) =
+                    (source.pipelines.get_mut(&pipeline), repository)
+                {
+                    memory_pipeline.repository.get_or_insert(repository);
+                }
+
+                source.insert_access(
+                    id,
+                    AccessLogEntry {
+                        timestamp,
+                        pipeline,
+                        job,
+                    },
+                    is_relevant,
+                );
+            }
+        }
+
+        for times in source.access_times.values_mut() {
+            times.sort_unstable();
+        }
+
+        let merges: Vec<(MergeRequestEventID, String, Option<String>, String)> =
+            sqlx::query_as("SELECT eventID,sourceBranch,status,action FROM MergeRequestEvent")
+                .fetch_all(con)
+                .await?;
+
+        for (id, source_branch, status, action) in merges {
+            // Events without a status fail to load from the database and are thus left out
+            if let Some(status) = status {
+                source.merges.insert(
+                    id,
+                    MergeRequestEvent {
+                        source_branch,
+                        status,
+                        action,
+                    },
+                );
+            }
+        }
+
+        source.index_refs();
+        source.size_source = Some(database);
+
+        Ok(source)
+    }
+
+    fn push_event(&mut self, timestamp: i64, kind: SimulationEventKind, key: i64) {
+        self.events.push(SimulationEvent {
+            id: self.events.len() as i64,
+            timestamp,
+            kind,
+            key,
+        });
+    }
+
+    fn insert_access(&mut self, id: AccessLogEntryID, entry: AccessLogEntry, is_relevant: bool) {
+        if is_relevant {
+            self.access_times
+                .entry(entry.pipeline)
+                .or_default()
+                .push(entry.timestamp);
+        }
+
+        self.accesses.insert(id, entry);
+    }
+
+    fn index_refs(&mut self) {
+        for (id, pipeline) in self.pipelines.iter() {
+            if let Some(pipeline_ref) = pipeline.pipeline_ref.as_ref() {
+                self.pipelines_by_ref
+                    .entry(pipeline_ref.clone())
+                    .or_default()
+                    .push(*id);
+            }
+        }
+    }
+
+    fn update_pipeline(&mut self, id: PipelineID, record: &TraceRecord) -> Result<()> {
+        let pipeline = self.pipelines.entry(id).or_default();
+
+        match record.kind {
+            TraceRecordKind::Created => pipeline.created_at = Some(record.timestamp),
+            TraceRecordKind::Finished => pipeline.finished_at = Some(record.timestamp),
+            _ => {}
+        }
+
+        if let Some(size) = record.size {
+            pipeline.size = Some(size);
+        }
+
+        if let Some(pipeline_ref) = record.pipeline_ref.as_ref() {
+            pipeline.pipeline_ref = Some(pipeline_ref.clone());
+        }
+
+        if let Some(repository) = record.repository.as_ref() {
+            pipeline.repository = Some(repository.clone());
+        }
+
+        if let Some(status) = record.status.as_ref() {
+            pipeline.status = Some(status.parse()?);
+            pipeline.raw_status = Some(status.clone());
+        }
+
+        Ok(())
+    }
+
+    fn memory_pipeline(&self, id: PipelineID) -> Result<&MemoryPipeline> {
+        self.pipelines
+            .get(&id)
+            .ok_or_else(|| anyhow!("Pipeline {} not found", id))
+    }
+
+    /// Converts the pipeline, failing if any of the metadata is missing.
+    /// A missing status is left empty unless it is required, flat traces may omit it.
+    fn to_pipeline(
+        id: PipelineID,
+        pipeline: &MemoryPipeline,
+        require_status: bool,
+    ) -> Result<Pipeline> {
+        let missing = |field| anyhow!("Pipeline {} has no {}", id, field);
+        let raw_status = match pipeline.raw_status.clone() {
+            Some(raw_status) => raw_status,
+            None if require_status => return Err(missing("status")),
+            None => String::new(),
+        };
+
+        Ok(Pipeline {
+            id,
+            jobs: pipeline.jobs.clone().ok_or_else(|| missing("jobs"))?,
+            created_at: pipeline
+                .created_at
+                .ok_or_else(|| missing("creation time"))?,
+            duration: pipeline.duration.ok_or_else(|| missing("duration"))?,
+            raw_status,
+        })
+    }
+}
+
+#[async_trait]
+impl TraceSource for MemoryTraceSource {
+    fn events(&self) -> BoxStream<'_, Result<SimulationEvent>> {
+        stream::iter(self.events.iter().map(|e| Ok(*e))).boxed()
+    }
+
+    async fn event_count(&self) -> Result<u64> {
+        Ok(self.events.len().try_into()?)
+    }
+
+    async fn access_log_entry(&self, id: AccessLogEntryID) -> Result<AccessLogEntry> {
+        self.accesses
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Access {} not found", id))
+    }
+
+    async fn merge_request_event(&self, id: MergeRequestEventID) -> Result<MergeRequestEvent> {
+        self.merges
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Merge request event {} not found", id))
+    }
+
+    async fn pipeline(&self, id: PipelineID) -> Result<Pipeline> {
+        Self::to_pipeline(id, self.memory_pipeline(id)?, true)
+    }
+
+    async fn pipelines_for_ref(&self, pipeline_ref: String) -> Result<Vec<Pipeline>> {
+        self.pipelines_by_ref
+            .get(&pipeline_ref)
+            .map_or(&[][..], |ids| &ids[..])
+            .iter()
+            // Merges only need the pipelines of the ref, not their status
+            .map(|id| Self::to_pipeline(*id, self.memory_pipeline(*id)?, false))
+            .collect()
+    }
+
+    fn all_pipelines(&self) -> BoxStream<'_, Result<Pipeline>> {
+        let mut ids = self
+            .pipelines
+            .iter()
+            .filter(|(_, p)| p.created_at.is_some_and(|t| t > 0))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        stream::iter(
+            ids.into_iter()
+                .map(move |id| Self::to_pipeline(id, &self.pipelines[&id], true)),
+        )
+        .boxed()
+    }
+
+    async fn has_pipeline_metadata(&self, id: PipelineID) -> Result<bool> {
+        Ok(self.memory_pipeline(id)?.created_at.is_some())
+    }
+
+    async fn pipeline_size(&self, id: PipelineID) -> Result<Option<i64>> {
+        match (self.memory_pipeline(id)?.size, self.size_source.as_ref()) {
+            (None, Some(size_source)) => size_source.pipeline_size(id).await,
+            (size, _) => Ok(size),
+        }
+    }
+
+    /// Pipelines of flat trace files consist of a single unnamed job
+    async fn job_sizes(&self, id: PipelineID) -> Result<Option<Vec<(String, i64)>>> {
+        match (self.memory_pipeline(id)?.size, self.size_source.as_ref()) {
+            (None, Some(size_source)) => size_source.job_sizes(id).await,
+            (size, _) => Ok(size.map(|size| vec![(String::new(), size)])),
+        }
+    }
+
+    async fn pipeline_status(&self, id: PipelineID) -> Result<PipelineStatus> {
+        self.memory_pipeline(id)?
+            .status
+            .ok_or_else(|| anyhow!("Pipeline {} has no status", id))
+    }
+
+    async fn pipeline_ref(&self, id: PipelineID) -> Result<Option<String>> {
+        Ok(self.pipelines.get(&id).and_then(|p| p.pipeline_ref.clone()))
+    }
+
+    async fn pipeline_repository(&self, id: PipelineID) -> Result<Option<String>> {
+        Ok(self.pipelines.get(&id).and_then(|p| p.repository.clone()))
+    }
+
+    async fn pipeline_duration(&self, id: PipelineID) -> Result<i64> {
+        Ok(self.memory_pipeline(id)?.duration.unwrap_or(0))
+    }
+
+    async fn accesses_after_timestamp(&self, id: PipelineID, timestamp: i64) -> Result<Vec<i64>> {
+        Ok(self
+            .access_times
+            .get(&id)
+            .map_or(&[][..], |t| &t[..])
+            .iter()
+            .rev()
+            .take_while(|t| **t > timestamp)
+            .copied()
+            .collect())
+    }
+}
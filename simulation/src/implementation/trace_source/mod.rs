@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+
+use super::{
+    data_source::{AccessLogEntry, MergeRequestEvent, Pipeline, PipelineStatus, SimulationEvent},
+    AccessLogEntryID, MergeRequestEventID, PipelineID,
+};
+
+mod memory;
+mod sqlite;
+
+pub use memory::MemoryTraceSource;
+pub use sqlite::SqliteTraceSource;
+
+/// Complete row of the `JobSizeSample` table
+#[derive(sqlx::FromRow)]
+#[sqlx(rename_all = "camelCase")]
+pub struct JobSizeSampleRecord {
+    pub id: i64,
+    pub environment: String,
+    pub test_suite: String,
+    pub bytes: i64,
+}
+
+/// Complete row of the `Pipeline` table
+#[derive(sqlx::FromRow)]
+pub struct PipelineRecord {
+    pub id: PipelineID,
+    pub status: Option<String>,
+    pub duration: Option<i64>,
+    #[sqlx(rename = "createdAt")]
+    pub created_at: Option<i64>,
+    #[sqlx(rename = "finishedAt")]
+    pub finished_at: Option<i64>,
+    #[sqlx(rename = "ref")]
+    pub pipeline_ref: Option<String>,
+    pub jobs: Option<String>,
+}
+
+/// Complete row of the `MergeRequestEvent` table
+#[derive(sqlx::FromRow)]
+pub struct MergeRequestEventRecord {
+    #[sqlx(rename = "eventID")]
+    pub id: MergeRequestEventID,
+    #[sqlx(rename = "mergeRequestID")]
+    pub merge_request: i64,
+    pub status: Option<String>,
+    #[sqlx(rename = "sourceBranch")]
+    pub source_branch: String,
+    #[sqlx(rename = "targetBranch")]
+    pub target_branch: String,
+    pub action: String,
+    pub timestamp: Option<i64>,
+}
+
+/// Row of the `AccessLog` table, omitting the columns which are never relevant to the simulation
+/// (path, referee, user agent and file)
+#[derive(sqlx::FromRow)]
+#[sqlx(rename_all = "camelCase")]
+pub struct AccessLogRecord {
+    pub id: AccessLogEntryID,
+    pub timestamp: i64,
+    pub method: String,
+    pub status: i64,
+    pub bytes: i64,
+    pub is_automatic: bool,
+    pub is_irrelevant: bool,
+    pub repository: Option<String>,
+    pub pipeline: Option<PipelineID>,
+    pub job: Option<String>,
+}
+
+/// Stream failing right away, used by sources which do not retain the complete records
+fn records_unavailable<'a, T: Send + 'a>() -> BoxStream<'a, Result<T>> {
+    stream::once(async {
+        Err(anyhow!(
+            "The trace does not retain complete records, only SQLite databases support this"
+        ))
+    })
+    .boxed()
+}
+
+/// Storage backend providing the recorded events and pipeline metadata a simulation is based on
+#[async_trait]
+pub trait TraceSource: Send + Sync {
+    /// All events ordered by their timestamp
+    fn events(&self) -> BoxStream<'_, Result<SimulationEvent>>;
+
+    async fn event_count(&self) -> Result<u64>;
+
+    async fn access_log_entry(&self, id: AccessLogEntryID) -> Result<AccessLogEntry>;
+
+    async fn merge_request_event(&self, id: MergeRequestEventID) -> Result<MergeRequestEvent>;
+
+    async fn pipeline(&self, id: PipelineID) -> Result<Pipeline>;
+
+    async fn pipelines_for_ref(&self, pipeline_ref: String) -> Result<Vec<Pipeline>>;
+
+    /// All pipelines with known metadata
+    fn all_pipelines(&self) -> BoxStream<'_, Result<Pipeline>>;
+
+    /// Whether metadata for the pipeline is available, pipelines may also only be known from accesses to them
+    async fn has_pipeline_metadata(&self, id: PipelineID) -> Result<bool>;
+
+    /// Size of the pipeline in bytes or `None` if it can not be determined
+    async fn pipeline_size(&self, id: PipelineID) -> Result<Option<i64>>;
+
+    /// Name and size in bytes of each job of the pipeline or `None` if the size of any job can not be determined.
+    /// The sizes add up to the size of the pipeline.
+    async fn job_sizes(&self, id: PipelineID) -> Result<Option<Vec<(String, i64)>>>;
+
+    async fn pipeline_status(&self, id: PipelineID) -> Result<PipelineStatus>;
+
+    /// Ref (branch or tag) the pipeline ran on or `None` if it is unknown
+    async fn pipeline_ref(&self, id: PipelineID) -> Result<Option<String>>;
+
+    /// Repository the artifacts of the pipeline have first been accessed through or `None` if it is unknown
+    async fn pipeline_repository(&self, id: PipelineID) -> Result<Option<String>>;
+
+    /// Duration it took the pipeline to run in seconds, pipelines without a known duration are treated as instant
+    async fn pipeline_duration(&self, id: PipelineID) -> Result<i64>;
+
+    /// Timestamps of all relevant accesses to the pipeline after the given timestamp, ordered from latest to earliest
+    async fn accesses_after_timestamp(&self, id: PipelineID, timestamp: i64) -> Result<Vec<i64>>;
+
+    /// All job size samples ordered by their ID, used to export modified copies of the trace
+    fn job_size_sample_records(&self) -> BoxStream<'_, Result<JobSizeSampleRecord>> {
+        records_unavailable()
+    }
+
+    /// All pipelines ordered by their ID, including those without metadata
+    fn pipeline_records(&self) -> BoxStream<'_, Result<PipelineRecord>> {
+        records_unavailable()
+    }
+
+    /// All merge request events ordered by their ID
+    fn merge_request_event_records(&self) -> BoxStream<'_, Result<MergeRequestEventRecord>> {
+        records_unavailable()
+    }
+
+    /// All access log entries ordered by their ID, including irrelevant ones
+    fn access_log_records(&self) -> BoxStream<'_, Result<AccessLogRecord>> {
+        records_unavailable()
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::Result;
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use std::convert::TryInto;
+
+use super::{
+    super::{
+        data_source::{
+            AccessLogEntry, MergeRequestEvent, Pipeline, PipelineStatus, SimulationEvent,
+        },
+        size_sampler::{JobSizeSampler, SizeStrategy},
+        AccessLogEntryID, MergeRequestEventID, PipelineID,
+    },
+    AccessLogRecord, JobSizeSampleRecord, MergeRequestEventRecord, PipelineRecord, TraceSource,
+};
+
+/// Trace stored in the SQLite database created by the `big-data-pipeline`.
+/// Pipeline sizes are sampled from the recorded sizes of their jobs.
+pub struct SqliteTraceSource {
+    con: SqlitePool,
+    sampler: Mutex<JobSizeSampler>,
+}
+
+impl SqliteTraceSource {
+    pub async fn open(database: &str, seed: u64, size_strategy: SizeStrategy) -> Result<Self> {
+        let con = SqlitePoolOptions::new()
+            .min_connections(64)
+            .max_connections(100)
+            .connect(database)
+            .await?;
+
+        Ok(Self {
+            con,
+            sampler: Mutex::new(JobSizeSampler::new(seed, size_strategy)),
+        })
+    }
+
+    pub(in crate::implementation) fn connection(&self) -> &SqlitePool {
+        &self.con
+    }
+}
+
+#[async_trait]
+impl TraceSource for SqliteTraceSource {
+    fn events(&self) -> BoxStream<'_, Result<SimulationEvent>> {
+        sqlx::query_as("SELECT * FROM SimulationEvent ORDER BY timestamp")
+            .fetch(&self.con)
+            .map(|event| Ok(event?))
+            .boxed()
+    }
+
+    async fn event_count(&self) -> Result<u64> {
+        let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM SimulationEvent")
+            .fetch_one(&self.con)
+            .await?;
+        Ok(row.0.try_into()?)
+    }
+
+    async fn access_log_entry(&self, id: AccessLogEntryID) -> Result<AccessLogEntry> {
+        Ok(
+            sqlx::query_as("SELECT timestamp,pipeline,job FROM AccessLog WHERE id=$1")
+                .bind(id)
+                .fetch_one(&self.con)
+                .await?,
+        )
+    }
+
+    async fn merge_request_event(&self, id: MergeRequestEventID) -> Result<MergeRequestEvent> {
+        Ok(sqlx::query_as(
+            "SELECT sourceBranch,status,action FROM MergeRequestEvent WHERE eventID=$1",
+        )
+        .bind(id)
+        .fetch_one(&self.con)
+        .await?)
+    }
+
+    async fn pipeline(&self, id: PipelineID) -> Result<Pipeline> {
+        Ok(
+            sqlx::query_as("SELECT id,jobs,status,duration,createdAt FROM Pipeline WHERE id=$1")
+                .bind(id)
+                .fetch_one(&self.con)
+                .await?,
+        )
+    }
+
+    async fn pipelines_for_ref(&self, pipeline_ref: String) -> Result<Vec<Pipeline>> {
+        Ok(
+            sqlx::query_as("SELECT id,jobs,status,duration,createdAt FROM Pipeline WHERE ref=$1")
+                .bind(pipeline_ref)
+                .fetch_all(&self.con)
+                .await?,
+        )
+    }
+
+    fn all_pipelines(&self) -> BoxStream<'_, Result<Pipeline>> {
+        sqlx::query_as("SELECT * FROM Pipeline WHERE createdAt > 0")
+            .fetch(&self.con)
+            .map(|pipeline| Ok(pipeline?))
+            .boxed()
+    }
+
+    async fn has_pipeline_metadata(&self, id: PipelineID) -> Result<bool> {
+        let row: (Option<i64>,) = sqlx::query_as("SELECT createdAt FROM Pipeline WHERE id=$1")
+            .bind(id)
+            .fetch_one(&self.con)
+            .await?;
+
+        Ok(row.0 != None)
+    }
+
+    async fn pipeline_size(&self, id: PipelineID) -> Result<Option<i64>> {
+        Ok(self
+            .job_sizes(id)
+            .await?
+            .map(|sizes| sizes.iter().map(|(_, size)| size).sum()))
+    }
+
+    async fn job_sizes(&self, id: PipelineID) -> Result<Option<Vec<(String, i64)>>> {
+        let mut sizes = Vec::new();
+        let mut sampler = self.sampler.lock().await;
+
+        let mut missed_count = 0;
+        for (index, job) in self.pipeline(id).await?.jobs.split(";").enumerate() {
+            match sampler.sample(id, index, job, &self.con).await {
+                Ok(size) => sizes.push((job.to_owned(), size)),
+                Err(_e) => {
+                    missed_count += 1;
+                    // eprintln!("Ignoring job {} of pipeline {}: {:?}", job, id, e)
+                }
+            }
+        }
+
+        if missed_count > 0 {
+            Ok(None)
+        } else {
+            Ok(Some(sizes))
+        }
+    }
+
+    async fn pipeline_status(&self, id: PipelineID) -> Result<PipelineStatus> {
+        let row: (String,) = sqlx::query_as("SELECT status FROM Pipeline WHERE id=$1")
+            .bind(id)
+            .fetch_one(&self.con)
+            .await?;
+
+        Ok(PipelineStatus::from_string(&row.0))
+    }
+
+    async fn pipeline_ref(&self, id: PipelineID) -> Result<Option<String>> {
+        let row: Option<(Option<String>,)> = sqlx::query_as("SELECT ref FROM Pipeline WHERE id=$1")
+            .bind(id)
+            .fetch_optional(&self.con)
+            .await?;
+
+        Ok(row.and_then(|r| r.0))
+    }
+
+    async fn pipeline_repository(&self, id: PipelineID) -> Result<Option<String>> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT repository FROM AccessLog WHERE pipeline=$1 AND repository IS NOT NULL ORDER BY id LIMIT 1")
+            .bind(id)
+            .fetch_optional(&self.con)
+            .await?;
+
+        Ok(row.map(|r| r.0))
+    }
+
+    async fn pipeline_duration(&self, id: PipelineID) -> Result<i64> {
+        let row: (Option<i64>,) = sqlx::query_as("SELECT duration FROM Pipeline WHERE id=$1")
+            .bind(id)
+            .fetch_one(&self.con)
+            .await?;
+
+        Ok(row.0.unwrap_or(0))
+    }
+
+    async fn accesses_after_timestamp(&self, id: PipelineID, timestamp: i64) -> Result<Vec<i64>> {
+        let rows: Vec<(i64,)> = sqlx::query_as("SELECT timestamp FROM AccessLog WHERE pipeline=$1 AND timestamp>$2 AND NOT isIrrelevant AND NOT isAutomatic ORDER BY timestamp DESC")
+            .bind(id)
+            .bind(timestamp)
+            .fetch_all(&self.con)
+            .await?;
+
+        Ok(rows.into_iter().map(|r| r.0).collect())
+    }
+
+    fn job_size_sample_records(&self) -> BoxStream<'_, Result<JobSizeSampleRecord>> {
+        sqlx::query_as("SELECT id, environment, testSuite, bytes FROM JobSizeSample ORDER BY id")
+            .fetch(&self.con)
+            .map(|record| Ok(record?))
+            .boxed()
+    }
+
+    fn pipeline_records(&self) -> BoxStream<'_, Result<PipelineRecord>> {
+        sqlx::query_as("SELECT id, status, duration, createdAt, finishedAt, ref, jobs FROM Pipeline ORDER BY id")
+            .fetch(&self.con)
+            .map(|record| Ok(record?))
+            .boxed()
+    }
+
+    fn merge_request_event_records(&self) -> BoxStream<'_, Result<MergeRequestEventRecord>> {
+        sqlx::query_as("SELECT eventID, mergeRequestID, status, sourceBranch, targetBranch, action, timestamp FROM MergeRequestEvent ORDER BY eventID")
+            .fetch(&self.con)
+            .map(|record| Ok(record?))
+            .boxed()
+    }
+
+    fn access_log_records(&self) -> BoxStream<'_, Result<AccessLogRecord>> {
+        sqlx::query_as("SELECT id, timestamp, method, status, bytes, isAutomatic, isIrrelevant, repository, pipeline, job FROM AccessLog ORDER BY id")
+            .fetch(&self.con)
+            .map(|record| Ok(record?))
+            .boxed()
+    }
+}
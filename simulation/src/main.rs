@@ -1,7 +1,7 @@
 #![feature(map_first_last)]
 #![feature(result_flattening)]
 
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 use anyhow::Result;
 use async_std::task;
@@ -9,53 +9,149 @@ use bytesize::ByteSize;
 use clap::Clap;
 
 mod algorithms;
+mod config;
 mod implementation;
 mod opts;
+mod report;
+mod sweep;
+mod workload;
 
-use implementation::{DataSource, MLGenerator, Simulation, StaticMLGenerator};
+use config::{AlgorithmChain, ExperimentConfig};
+use implementation::{
+    Anonymizer, DataSource, MLGenerator, Simulation, SizeCache, StaticMLGenerator, TenantConfig,
+};
 use indicatif::MultiProgress;
-use opts::{build_algorithm, Opts, SubCommand};
+use opts::{Opts, SubCommand};
+use rand::{distributions::Alphanumeric, Rng};
+use report::RunReport;
+use workload::{WorkloadConfig, WorkloadGenerator};
 
 // TODO Idea: Weighted/Cost based algorithm
 
-// IDEA: Event based cleanup algorithm (more specifically: onMergeEvent)
-//       Would at least be interesting in terms of disk usage target
-
 pub struct SimulationSpecification {
     name: String,
-    algorithms: Vec<String>,
+    algorithms: AlgorithmChain,
     storage_limit: ByteSize,
     output_path: PathBuf,
 }
 
+/// Opens the database and samples the size of all pipelines, using the size cache if enabled
+async fn prepare_data_source(opts: &Opts, seed: u64) -> Result<(DataSource, ByteSize)> {
+    let mut data_source = if opts.preload {
+        eprintln!("Preloading events and metadata ...");
+        DataSource::preload(&opts.database_path, seed, opts.size_strategy()).await?
+    } else {
+        DataSource::open(&opts.database_path, seed, opts.size_strategy()).await?
+    };
+    data_source.set_ref_classifier(opts.ref_classifier()?);
+
+    let cache = if opts.size_cache {
+        Some(SizeCache::for_database(
+            &opts.database_path,
+            seed,
+            opts.size_strategy(),
+        )?)
+    } else {
+        None
+    };
+
+    let cached_table = cache.as_ref().map(|c| c.load()).transpose()?.flatten();
+    let is_cached = cached_table.is_some();
+
+    if let Some(table) = cached_table {
+        eprintln!("Using cached pipeline size samples ...");
+        data_source.import_size_table(table).await;
+    } else {
+        eprintln!("Pre-populating pipeline size samples ...");
+    }
+
+    let total_size = data_source.populate_size_samples().await?;
+
+    if let (Some(cache), false) = (cache, is_cached) {
+        cache.store(&data_source.size_table().await)?;
+    }
+
+    data_source.freeze(opts.job_level).await?;
+
+    Ok((data_source, total_size))
+}
+
 async fn run_simulations(
-    database_path: String,
+    opts: &Opts,
     seed: u64,
     specifications: Vec<SimulationSpecification>,
-) -> Result<()> {
+) -> Result<Vec<RunReport>> {
     let progress_bar = MultiProgress::new();
     let mut handles = Vec::new();
 
-    eprintln!("Pre-populating pipeline size samples ...");
+    let tenants = opts
+        .tenants
+        .as_ref()
+        .map(|path| TenantConfig::load(path))
+        .transpose()?
+        .map(Arc::new);
 
-    let data_source = DataSource::open(&database_path, seed).await?;
-    let total_size = data_source.populate_size_samples().await?;
+    let (data_source, total_size) = prepare_data_source(opts, seed).await?;
     let event_count = data_source.event_count().await?;
 
     eprintln!("Simulating a total pipeline volume of {} and {} events...", total_size, event_count);
 
     for specification in specifications {
-        let simulation = Simulation::prepare(data_source.clone(), &progress_bar).await?;
-        let algorithm = build_algorithm(&specification.algorithms, seed);
+        let mut simulation = Simulation::prepare(data_source.clone(), &progress_bar).await?;
+        let tenants = tenants.clone();
 
         simulation.set_name(&specification.name);
 
+        if opts.trace_evictions {
+            simulation.enable_eviction_trace();
+        }
+        simulation.set_cleanup_mode(opts.cleanup_mode);
+
+        if opts.job_level {
+            simulation.enable_job_level();
+        }
+
         handles.push(task::spawn(async move {
-            simulation
-                .run(algorithm, specification.storage_limit)
-                .await
-                .map(|statistics| statistics.write_csv(specification.output_path))
-                .flatten()
+            let algorithms = &specification.algorithms;
+            let statistics = match tenants {
+                Some(tenants) => {
+                    simulation
+                        .run_tenants(
+                            tenants,
+                            || (algorithms.build(seed), algorithms.build_rules()),
+                            specification.storage_limit,
+                        )
+                        .await
+                }
+                None => {
+                    simulation
+                        .run(
+                            algorithms.build(seed),
+                            algorithms.build_rules(),
+                            specification.storage_limit,
+                        )
+                        .await
+                }
+            };
+
+            statistics.and_then(|statistics| {
+                let report = RunReport {
+                    name: specification.name,
+                    algorithms: specification.algorithms.name(),
+                    seed,
+                    storage_limit: specification.storage_limit.as_u64(),
+                    summary: statistics.summary(),
+                };
+
+                report.write_json(specification.output_path.with_extension("json"))?;
+                statistics
+                    .write_eviction_trace(specification.output_path.with_extension("trace.csv"))?;
+                statistics
+                    .write_tenant_csv(specification.output_path.with_extension("tenants.csv"))?;
+                statistics.write_csv(specification.output_path)?;
+
+                Ok(report)
+            })
         }))
     }
 
@@ -64,11 +160,12 @@ async fn run_simulations(
     })
     .await;
 
+    let mut reports = Vec::with_capacity(handles.len());
     for handle in handles {
-        handle.await?;
+        reports.push(handle.await?);
     }
 
-    Ok(())
+    Ok(reports)
 }
 
 #[async_std::main]
@@ -78,31 +175,111 @@ async fn main() -> Result<()> {
     let opts: Opts = Opts::parse();
     let mut output_folder = opts.output_directory.clone();
 
-    match opts.subcommand {
+    match opts.subcommand.clone() {
         SubCommand::OneShot(one_shot_opts) => {
-            let specification = one_shot_opts.specification(output_folder);
-            run_simulations(opts.database_path, opts.seed, vec![specification]).await?;
+            let specification = one_shot_opts.specification(output_folder)?;
+            run_simulations(&opts, opts.seed, vec![specification]).await?;
         }
         SubCommand::Batch(batch_opts) => {
             output_folder.push("batch");
-            let specifications = batch_opts.specifications(output_folder);
-            run_simulations(opts.database_path, opts.seed, specifications).await?;
+            let specifications = batch_opts.specifications(output_folder.clone())?;
+            let reports = run_simulations(&opts, opts.seed, specifications).await?;
+            report::write_summary_csv(output_folder.join("summary.csv"), &reports)?;
         }
         SubCommand::SizeRamp(ramp_opts) => {
             output_folder.push("size-ramp");
-            let specifications = ramp_opts.specifications(output_folder);
-            run_simulations(opts.database_path, opts.seed, specifications).await?;
+            let specifications = ramp_opts.specifications(output_folder.clone())?;
+            let reports = run_simulations(&opts, opts.seed, specifications).await?;
+            report::write_summary_csv(output_folder.join("summary.csv"), &reports)?;
+        }
+        SubCommand::Experiment(experiment_opts) => {
+            let config = ExperimentConfig::load(&experiment_opts.config)?;
+            let seed = config.seed.unwrap_or(opts.seed);
+
+            if let Some(name) = experiment_opts.config.file_stem() {
+                output_folder.push(name);
+            }
+
+            let specifications = config.specifications(output_folder.clone());
+            let reports = run_simulations(&opts, seed, specifications).await?;
+            report::write_summary_csv(output_folder.join("summary.csv"), &reports)?;
+        }
+        SubCommand::Sweep(sweep_opts) => {
+            output_folder.push("sweep");
+
+            let configurations = sweep::generate_configurations(
+                &sweep_opts.ranges(),
+                sweep_opts.strategy,
+                sweep_opts.samples,
+                opts.seed,
+            );
+
+            let specifications = configurations
+                .iter()
+                .enumerate()
+                .map(|(i, configuration)| SimulationSpecification {
+                    name: format!("SCORE-{}", i),
+                    algorithms: configuration.algorithms(format!("SCORE-{}", i)),
+                    storage_limit: sweep_opts.size_limit(),
+                    output_path: output_folder.join(format!("{}.csv", i)),
+                })
+                .collect();
+
+            let reports = run_simulations(&opts, opts.seed, specifications).await?;
+            sweep::write_summary(output_folder.join("sweep.csv"), &configurations, &reports)?;
         }
         SubCommand::GenerateML(_generate_opts) => {
             output_folder.push("ml-data.csv");
-            let generator = MLGenerator::prepare(&opts.database_path, opts.seed).await?;
+            let generator = MLGenerator::prepare(
+                &opts.database_path,
+                opts.seed,
+                opts.size_strategy(),
+                opts.ref_classifier()?,
+            )
+            .await?;
             generator.generate().await?;
         }
         SubCommand::GenerateStaticML(_generate_opts) => {
             output_folder.push("ml-data.csv");
-            let generator = StaticMLGenerator::new(&opts.database_path, opts.seed).await?;
+            let generator = StaticMLGenerator::new(
+                &opts.database_path,
+                opts.seed,
+                opts.size_strategy(),
+                opts.ref_classifier()?,
+            )
+            .await?;
             generator.generate().await?;
         }
+        SubCommand::GenerateWorkload(workload_opts) => {
+            let config = match workload_opts.config {
+                Some(path) => WorkloadConfig::load(&path)?,
+                None => WorkloadConfig::default(),
+            };
+
+            WorkloadGenerator::new(config, opts.seed)
+                .generate(&workload_opts.output)
+                .await?;
+        }
+        SubCommand::Anonymize(anonymize_opts) => {
+            let mut rng = rand::thread_rng();
+            let salt = anonymize_opts.salt.unwrap_or_else(|| {
+                (&mut rng)
+                    .sample_iter(&Alphanumeric)
+                    .take(32)
+                    .map(char::from)
+                    .collect()
+            });
+            let time_shift = anonymize_opts
+                .time_shift
+                .unwrap_or_else(|| rng.gen_range(52..520) * 60 * 60 * 24 * 7);
+
+            let source =
+                DataSource::open(&opts.database_path, opts.seed, opts.size_strategy()).await?;
+
+            Anonymizer::new(source, salt, time_shift)
+                .export(&anonymize_opts.output)
+                .await?;
+        }
     }
 
     Ok(())
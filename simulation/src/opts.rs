@@ -1,28 +1,22 @@
+use anyhow::Result;
 use bytesize::ByteSize;
 use clap::Clap;
-use std::{collections::HashMap, path::PathBuf};
+use std::path::PathBuf;
 
 use crate::{
-    algorithms::*,
-    implementation::{CleanupAlgorithm, CleanupAttemptAlgorithm, FallbackCleanupAlgorithm},
+    config::AlgorithmChain,
+    implementation::{CleanupMode, RefClassifier, SizeDistribution, SizeStrategy},
+    sweep::{ParameterRange, SweepStrategy},
     SimulationSpecification,
 };
 
-macro_rules! algorithm_map {
-    ($t: ty, $( $name: expr => $algorithm: expr ),*) => {{
-        let mut map: HashMap<String, Box<$t>> = ::std::collections::HashMap::new();
-        $(map.insert($name.to_owned(), Box::new($algorithm)); )*
-        map
-    }}
-}
-
 #[derive(Clap, Clone)]
 #[clap(version = "1.0", author = "Til B. <til@blechschmidt.de>")]
 pub struct Opts {
     /// Seed to use for the simulation and any RNG based algorithms. Note that each algorithm will have its own PRNG instance based on the seed.
     #[clap(short, long, default_value = "1337")]
     pub seed: u64,
-    /// Database that serves as the input to the simulation
+    /// Database or flat JSONL/CSV trace file that serves as the input to the simulation
     #[clap(short, long, default_value = "../data/out/simulation.db")]
     pub database_path: String,
     /// Directory in which to store the CSV statistics
@@ -33,18 +27,126 @@ pub struct Opts {
         default_value = "../data/out/simulation/"
     )]
     pub output_directory: PathBuf,
+    /// Additionally write a trace of every eviction next to the CSV statistics
+    #[clap(long)]
+    pub trace_evictions: bool,
+    /// Store sampled pipeline sizes next to the database and reuse them in subsequent runs with the same seed
+    #[clap(long)]
+    pub size_cache: bool,
+    /// Load all events and metadata of the database into memory before simulating instead of querying it for each event
+    #[clap(long)]
+    pub preload: bool,
+    /// Distribution used to derive job sizes from the size samples (empirical, log-normal, mean or median)
+    #[clap(long, default_value = "empirical")]
+    pub size_distribution: SizeDistribution,
+    /// Fall back to the size samples of the whole environment for test suites with too few samples
+    #[clap(long)]
+    pub environment_size_fallback: bool,
+    /// When to clean up storage: immediate (whenever the limit is exceeded), watermark:<high>:<low> (e.g. watermark:0.95:0.8) or periodic:<hours>[:<low>] (e.g. periodic:24:0.8). Watermarks are fractions of the storage limit.
+    #[clap(long, default_value = "immediate")]
+    pub cleanup_mode: CleanupMode,
+    /// Store and evict the artifacts of each job individually instead of whole pipelines, required by job algorithms (LJ, JOB.<pattern>)
+    #[clap(long)]
+    pub job_level: bool,
+    /// TOML file assigning refs to categories by regular expressions, used by the REF scoring component and the ML data. Defaults to premaster, master, merge-release, release and merge-request.
+    #[clap(long, parse(from_os_str))]
+    pub ref_categories: Option<PathBuf>,
+    /// TOML file splitting the storage limit into per-repository or per-ref quotas and a shared pool
+    #[clap(long, parse(from_os_str))]
+    pub tenants: Option<PathBuf>,
 
     #[clap(subcommand)]
     pub subcommand: SubCommand,
 }
 
+impl Opts {
+    pub fn size_strategy(&self) -> SizeStrategy {
+        SizeStrategy {
+            distribution: self.size_distribution,
+            environment_fallback: self.environment_size_fallback,
+        }
+    }
+
+    pub fn ref_classifier(&self) -> Result<RefClassifier> {
+        match &self.ref_categories {
+            Some(path) => RefClassifier::load(path),
+            None => Ok(RefClassifier::default()),
+        }
+    }
+}
+
 #[derive(Clap, Clone)]
 pub enum SubCommand {
     OneShot(OneShotOpts),
     Batch(BatchOpts),
     SizeRamp(SizeRampOpts),
+    Experiment(ExperimentOpts),
+    Sweep(SweepOpts),
     GenerateML(GenerateML),
     GenerateStaticML(GenerateStaticML),
+    GenerateWorkload(GenerateWorkloadOpts),
+    Anonymize(AnonymizeOpts),
+}
+
+#[derive(Clap, Clone)]
+pub struct ExperimentOpts {
+    /// TOML file describing the simulation runs
+    #[clap(parse(from_os_str))]
+    pub config: PathBuf,
+}
+
+/// Runs the SCORE algorithm for a set of weight configurations.
+/// Weights are given as inclusive ranges in the format 'start:end:step' and default to the weights of SCORE.
+#[derive(Clap, Clone)]
+pub struct SweepOpts {
+    /// Size limit for the simulated disk in GB
+    size_limit: u64,
+    /// How configurations are drawn from the ranges (grid, random or latin-hypercube)
+    #[clap(long, default_value = "grid")]
+    pub strategy: SweepStrategy,
+    /// Number of configurations to draw when not using the grid strategy
+    #[clap(long, default_value = "100")]
+    pub samples: usize,
+    /// Status score of running pipelines
+    #[clap(long, default_value = "0", allow_hyphen_values = true)]
+    running: ParameterRange,
+    /// Status score of successful pipelines
+    #[clap(long, default_value = "45", allow_hyphen_values = true)]
+    success: ParameterRange,
+    /// Status score of failed pipelines
+    #[clap(long, default_value = "-5", allow_hyphen_values = true)]
+    failed: ParameterRange,
+    /// Status score of cancelled pipelines
+    #[clap(long, default_value = "0", allow_hyphen_values = true)]
+    cancelled: ParameterRange,
+    /// Score of pipelines whose branch has been merged
+    #[clap(long, default_value = "30", allow_hyphen_values = true)]
+    merged: ParameterRange,
+    /// Age in seconds at which a pipeline reaches the full age score
+    #[clap(long, default_value = "259200")]
+    age_threshold: ParameterRange,
+    /// Maximum age score
+    #[clap(long, default_value = "50", allow_hyphen_values = true)]
+    age_score: ParameterRange,
+}
+
+impl SweepOpts {
+    pub fn size_limit(&self) -> ByteSize {
+        ByteSize::gb(self.size_limit)
+    }
+
+    /// Parameter ranges in the order expected by `sweep::generate_configurations`
+    pub fn ranges(&self) -> Vec<ParameterRange> {
+        vec![
+            self.running,
+            self.success,
+            self.failed,
+            self.cancelled,
+            self.merged,
+            self.age_threshold,
+            self.age_score,
+        ]
+    }
 }
 
 #[derive(Clap, Clone)]
@@ -53,6 +155,31 @@ pub struct GenerateML {}
 #[derive(Clap, Clone)]
 pub struct GenerateStaticML {}
 
+/// Generates a synthetic database which can be used as an input to the simulation
+#[derive(Clap, Clone)]
+pub struct GenerateWorkloadOpts {
+    /// Path of the database to create
+    #[clap(parse(from_os_str))]
+    pub output: PathBuf,
+    /// TOML file with the parameters of the workload, unspecified parameters use their default values
+    #[clap(short, long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+}
+
+/// Writes a copy of the database with branch names, job names, paths and user agents anonymized and timestamps shifted
+#[derive(Clap, Clone)]
+pub struct AnonymizeOpts {
+    /// Path of the anonymized database to create
+    #[clap(parse(from_os_str))]
+    pub output: PathBuf,
+    /// Secret key for hashing names with HMAC-SHA256, use the same salt to get matching hashes across exports. Defaults to a random salt.
+    #[clap(long)]
+    pub salt: Option<String>,
+    /// Number of seconds to subtract from all timestamps. Defaults to a random number of whole weeks.
+    #[clap(long)]
+    pub time_shift: Option<i64>,
+}
+
 #[derive(Clap, Clone)]
 pub struct BatchOpts {
     /// Size limit for the simulated disk in GB
@@ -66,19 +193,19 @@ impl BatchOpts {
         ByteSize::gb(self.size_limit)
     }
 
-    pub fn specifications(self, output_folder: PathBuf) -> Vec<SimulationSpecification> {
+    pub fn specifications(self, output_folder: PathBuf) -> Result<Vec<SimulationSpecification>> {
         let storage_limit = self.size_limit();
         self.definitions
             .into_iter()
             .map(|definition| {
-                let algorithms = definition.split("-").map(|s| s.to_owned()).collect();
+                let algorithms = AlgorithmChain::from_definition(&definition)?;
 
-                SimulationSpecification {
+                Ok(SimulationSpecification {
                     storage_limit,
                     algorithms,
                     output_path: output_folder.join(format!("{}.csv", definition)),
                     name: definition,
-                }
+                })
             })
             .collect()
     }
@@ -95,16 +222,16 @@ pub struct SizeRampOpts {
 }
 
 impl SizeRampOpts {
-    pub fn specifications(self, output_folder: PathBuf) -> Vec<SimulationSpecification> {
+    pub fn specifications(self, output_folder: PathBuf) -> Result<Vec<SimulationSpecification>> {
         let mut specifications = Vec::new();
 
         for exp in self.lower_exponent..self.upper_exponent {
             let storage_limit = ByteSize::gb(2u64.pow(exp));
-            let limit_name = storage_limit.to_string().replace(" ", "").replace(".0", "");
+            let limit_name = storage_limit_name(storage_limit);
             let size_directory = output_folder.join(&limit_name);
 
             for definition in self.definitions.iter() {
-                let algorithms = definition.split("-").map(|s| s.to_owned()).collect();
+                let algorithms = AlgorithmChain::from_definition(definition)?;
                 let name = format!("{}-{}", definition, limit_name);
 
                 let output_path = size_directory.join(format!("{}.csv", definition));
@@ -118,7 +245,7 @@ impl SizeRampOpts {
             }
         }
 
-        specifications
+        Ok(specifications)
     }
 }
 
@@ -139,71 +266,17 @@ impl OneShotOpts {
         ByteSize::gb(self.size_limit)
     }
 
-    pub fn specification(self, output_folder: PathBuf) -> SimulationSpecification {
-        SimulationSpecification {
+    pub fn specification(self, output_folder: PathBuf) -> Result<SimulationSpecification> {
+        Ok(SimulationSpecification {
             name: self.algorithms.join("-"),
             storage_limit: self.size_limit(),
-            algorithms: self.algorithms,
+            algorithms: AlgorithmChain::from_names(&self.algorithms)?,
             output_path: output_folder.join(self.filename),
-        }
+        })
     }
 }
 
-pub fn build_algorithm(algorithms: &Vec<String>, seed: u64) -> Box<FallbackCleanupAlgorithm> {
-    let fallback_algorithm_string = algorithms
-        .last()
-        .expect("You must provide at least one algorithm");
-
-    let mut failable_algorithms = algorithm_map![dyn CleanupAttemptAlgorithm,
-        "MERGED" => BranchMergedAlgorithm {},
-        "LRU" => LRUAlgorithm {},
-        "MRU" => MRUAlgorithm {},
-        "MRU.2" => MRURangedAlgorithm::new(2),
-        "MRU.4" => MRURangedAlgorithm::new(4),
-        "MRU.8" => MRURangedAlgorithm::new(8),
-        "MRU.16" => MRURangedAlgorithm::new(16),
-        "MRU.32" => MRURangedAlgorithm::new(32),
-        "MRU.64" => MRURangedAlgorithm::new(64),
-        "LF" => LargestFirstAlgorithm {},
-        "SF" => SmallestFirstAlgorithm {},
-        "STATUS" => LayeredStatusAlgorithm {}
-    ];
-
-    let mut fallback_algorithms = algorithm_map![dyn CleanupAlgorithm,
-        "RAND" => RandomAlgorithm::new(seed),
-        "LIFO" => LIFOAlgorithm {},
-        "FIFO" => FIFOAlgorithm {},
-        "SCORE.DEFAULT" => ScoringAlgorithmManager::new(vec![
-            Box::new(StatusAlgorithm::default()),
-            Box::new(MergedAlgorithm::default()),
-            Box::new(AgeAlgorithm::default())
-        ]),
-        "SCORE" => ScoringAlgorithmManager::new(vec![
-            Box::new(StatusAlgorithm::new(0, 45, -5, 0)),
-            Box::new(MergedAlgorithm::new(30)),
-            Box::new(AgeAlgorithm::new(60 * 60 * 24 * 3, 50))
-        ])
-    ];
-
-    let algorithms = algorithms
-        .iter()
-        .take(algorithms.len() - 1)
-        .map(|key| {
-            failable_algorithms
-                .remove(key)
-                .expect(&format!("Algorithm '{}' not found!", key))
-        })
-        .collect::<Vec<Box<dyn CleanupAttemptAlgorithm>>>();
-
-    let fallback_algorithm = fallback_algorithms
-        .remove(fallback_algorithm_string)
-        .expect(&format!(
-            "Fallback algorithm '{}' not found!",
-            fallback_algorithm_string
-        ));
-
-    Box::new(FallbackCleanupAlgorithm::new(
-        algorithms,
-        fallback_algorithm,
-    ))
+/// Compact representation of a storage limit for use in file and directory names (e.g. `512GB`)
+pub fn storage_limit_name(storage_limit: ByteSize) -> String {
+    storage_limit.to_string().replace(" ", "").replace(".0", "")
 }
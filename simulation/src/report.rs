@@ -0,0 +1,58 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::{fmt, fs::create_dir_all, fs::File, io::Write, path::PathBuf};
+
+use crate::implementation::Summary;
+
+/// Summary of a simulation run including the parameters it was run with
+#[derive(Serialize)]
+pub struct RunReport {
+    pub name: String,
+    pub algorithms: String,
+    pub seed: u64,
+    /// Size limit of the simulated disk in bytes
+    pub storage_limit: u64,
+    #[serde(flatten)]
+    pub summary: Summary,
+}
+
+impl RunReport {
+    pub fn write_json(&self, path: PathBuf) -> Result<()> {
+        create_dir_all(path.parent().unwrap())?;
+        let f = File::create(path)?;
+        serde_json::to_writer_pretty(f, self)?;
+
+        Ok(())
+    }
+
+    pub fn csv_header() -> String {
+        format!(
+            "Name,Algorithms,Seed,Storage limit,{}",
+            Summary::csv_header()
+        )
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{}",
+            self.name, self.algorithms, self.seed, self.storage_limit, self.summary
+        )
+    }
+}
+
+/// Writes the reports of multiple runs into a single CSV file with one row per run
+pub fn write_summary_csv(path: PathBuf, reports: &[RunReport]) -> Result<()> {
+    create_dir_all(path.parent().unwrap())?;
+    let mut f = File::create(path)?;
+
+    writeln!(f, "{}", RunReport::csv_header())?;
+
+    for report in reports {
+        writeln!(f, "{}", report)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use anyhow::{anyhow, bail, Error, Result};
+use rand::{prelude::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::{fmt, fs::create_dir_all, fs::File, io::Write, path::PathBuf, str::FromStr};
+
+use crate::{
+    algorithms::Score,
+    config::{AlgorithmChain, FallbackAlgorithmSpecification, ScoringComponentSpecification},
+    report::RunReport,
+};
+
+/// Inclusive range of parameter values in the format `start:end:step` or just `value`
+#[derive(Clone, Copy, Debug)]
+pub struct ParameterRange {
+    start: i64,
+    end: i64,
+    step: i64,
+}
+
+impl ParameterRange {
+    pub fn values(&self) -> Vec<i64> {
+        let mut values = Vec::new();
+        let mut value = self.start;
+
+        while value <= self.end {
+            values.push(value);
+            value += self.step;
+        }
+
+        values
+    }
+}
+
+impl FromStr for ParameterRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let components = s
+            .split(':')
+            .map(|c| c.trim().parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (start, end, step) = match components[..] {
+            [value] => (value, value, 1),
+            [start, end] => (start, end, 1),
+            [start, end, step] => (start, end, step),
+            _ => bail!(
+                "Expected parameter range in the format 'start:end:step', got '{}'",
+                s
+            ),
+        };
+
+        if step <= 0 || end < start {
+            bail!("Parameter range '{}' does not contain any values", s);
+        }
+
+        Ok(Self { start, end, step })
+    }
+}
+
+/// How configurations are drawn from the parameter space
+#[derive(Clone, Copy, Debug)]
+pub enum SweepStrategy {
+    /// Cartesian product of all parameter ranges
+    Grid,
+    /// Uniformly sampled configurations
+    Random,
+    /// Latin hypercube sampling which spreads the samples evenly across each parameter range
+    LatinHypercube,
+}
+
+impl FromStr for SweepStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "grid" => Ok(Self::Grid),
+            "random" => Ok(Self::Random),
+            "latin-hypercube" => Ok(Self::LatinHypercube),
+            _ => Err(anyhow!(
+                "Unknown sweep strategy '{}' (expected grid, random or latin-hypercube)",
+                s
+            )),
+        }
+    }
+}
+
+/// Weights of a single `SCORE` configuration within a sweep
+#[derive(Clone, Copy, Debug)]
+pub struct SweepConfiguration {
+    running: Score,
+    success: Score,
+    failed: Score,
+    cancelled: Score,
+    merged: Score,
+    age_threshold: i64,
+    age_score: Score,
+}
+
+impl SweepConfiguration {
+    /// Builds a configuration from values ordered like the ranges passed to `generate_configurations`
+    fn from_values(values: &[i64]) -> Self {
+        Self {
+            running: values[0] as Score,
+            success: values[1] as Score,
+            failed: values[2] as Score,
+            cancelled: values[3] as Score,
+            merged: values[4] as Score,
+            age_threshold: values[5],
+            age_score: values[6] as Score,
+        }
+    }
+
+    pub fn algorithms(&self, name: String) -> AlgorithmChain {
+        AlgorithmChain {
+            protect: Vec::new(),
+            retention: Vec::new(),
+            jobs: Vec::new(),
+            attempts: Vec::new(),
+            fallback: FallbackAlgorithmSpecification::Score {
+                name: Some(name),
+                components: vec![
+                    ScoringComponentSpecification::Status {
+                        running: self.running,
+                        success: self.success,
+                        failed: self.failed,
+                        cancelled: self.cancelled,
+                    },
+                    ScoringComponentSpecification::Merged { score: self.merged },
+                    ScoringComponentSpecification::Age {
+                        threshold: self.age_threshold,
+                        score: self.age_score,
+                    },
+                ],
+            },
+        }
+    }
+
+    pub fn csv_header() -> &'static str {
+        "Running,Success,Failed,Cancelled,Merged,Age threshold,Age score"
+    }
+}
+
+impl fmt::Display for SweepConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{}",
+            self.running,
+            self.success,
+            self.failed,
+            self.cancelled,
+            self.merged,
+            self.age_threshold,
+            self.age_score
+        )
+    }
+}
+
+/// Draws configurations from the given ranges which have to be ordered like the fields of `SweepConfiguration`.
+/// The sample count is ignored for grid sweeps.
+pub fn generate_configurations(
+    ranges: &[ParameterRange],
+    strategy: SweepStrategy,
+    samples: usize,
+    seed: u64,
+) -> Vec<SweepConfiguration> {
+    let values = ranges.iter().map(|r| r.values()).collect::<Vec<_>>();
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let points: Vec<Vec<i64>> = match strategy {
+        SweepStrategy::Grid => values.iter().fold(vec![Vec::new()], |points, dimension| {
+            points
+                .iter()
+                .flat_map(|point| {
+                    dimension.iter().map(move |value| {
+                        let mut point = point.clone();
+                        point.push(*value);
+                        point
+                    })
+                })
+                .collect()
+        }),
+        SweepStrategy::Random => (0..samples)
+            .map(|_| {
+                values
+                    .iter()
+                    .map(|dimension| dimension[rng.gen_range(0..dimension.len())])
+                    .collect()
+            })
+            .collect(),
+        SweepStrategy::LatinHypercube => {
+            // Divide each dimension into one stratum per sample and assign each stratum to exactly one sample
+            let strata = values
+                .iter()
+                .map(|dimension| {
+                    let mut permutation = (0..samples).collect::<Vec<_>>();
+                    permutation.shuffle(&mut rng);
+
+                    permutation
+                        .into_iter()
+                        .map(|stratum| {
+                            let position = (stratum as f64 + rng.gen::<f64>()) / samples as f64;
+                            // The position may round up to 1.0 for the last stratum
+                            let index = (position * dimension.len() as f64) as usize;
+                            dimension[index.min(dimension.len() - 1)]
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+
+            (0..samples)
+                .map(|i| strata.iter().map(|dimension| dimension[i]).collect())
+                .collect()
+        }
+    };
+
+    points
+        .iter()
+        .map(|point| SweepConfiguration::from_values(point))
+        .collect()
+}
+
+pub fn write_summary(
+    path: PathBuf,
+    configurations: &[SweepConfiguration],
+    reports: &[RunReport],
+) -> Result<()> {
+    create_dir_all(path.parent().unwrap())?;
+    let mut f = File::create(path)?;
+
+    writeln!(
+        f,
+        "{},Missed fraction,Deleted count,Peak occupied storage",
+        SweepConfiguration::csv_header()
+    )?;
+
+    for (configuration, report) in configurations.iter().zip(reports) {
+        writeln!(
+            f,
+            "{},{},{},{}",
+            configuration,
+            report.summary.miss_fraction,
+            report.summary.deleted_count,
+            report.summary.peak_occupied_storage.as_u64()
+        )?;
+    }
+
+    Ok(())
+}
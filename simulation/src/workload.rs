@@ -0,0 +1,472 @@
+use anyhow::{bail, Result};
+use rand::{
+    distributions::WeightedIndex,
+    prelude::{Distribution, StdRng},
+    seq::SliceRandom,
+    Rng, SeedableRng,
+};
+use rand_distr::{Exp, LogNormal, Pareto, Poisson};
+use serde::Deserialize;
+use sqlx::{Connection, SqliteConnection};
+use std::{fs, path::Path};
+
+use crate::implementation::{create_database, SimulationEventKind};
+
+/// Log-normal distribution described by its median and the standard deviation of the underlying normal distribution
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct LogNormalParameters {
+    pub median: f64,
+    pub sigma: f64,
+}
+
+impl LogNormalParameters {
+    fn distribution(&self) -> Result<LogNormal<f64>> {
+        Ok(LogNormal::new(self.median.ln(), self.sigma)?)
+    }
+}
+
+/// Distribution of the delay in seconds between a pipeline finishing and it being accessed
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(tag = "curve")]
+pub enum AccessDecay {
+    #[serde(rename = "exponential")]
+    Exponential { mean: f64 },
+    /// Heavy tailed decay where most accesses happen after `scale` seconds
+    #[serde(rename = "pareto")]
+    Pareto { scale: f64, shape: f64 },
+}
+
+impl AccessDecay {
+    fn sample<R: Rng>(&self, rng: &mut R) -> Result<f64> {
+        Ok(match *self {
+            Self::Exponential { mean } => Exp::new(1.0 / mean)?.sample(rng),
+            Self::Pareto { scale, shape } => Pareto::new(scale, shape)?.sample(rng) - scale,
+        })
+    }
+}
+
+/// Relative frequencies of pipeline results
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct StatusWeights {
+    pub success: f64,
+    pub failed: f64,
+    pub canceled: f64,
+}
+
+/// Relative frequencies of the refs pipelines are run on
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct RefWeights {
+    pub master: f64,
+    pub premaster: f64,
+    pub release: f64,
+    pub feature: f64,
+}
+
+/// Parameters of a synthetic workload, all durations are given in seconds.
+///
+/// ```toml
+/// duration = 604800
+/// pipeline_interval = 300
+/// merge_probability = 0.9
+/// access_decay = { curve = "pareto", scale = 600, shape = 1.2 }
+/// job_size = { median = 200000000, sigma = 0.8 }
+/// ```
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct WorkloadConfig {
+    /// Timestamp of the first pipeline
+    pub start: i64,
+    /// Time span during which pipelines are created
+    pub duration: i64,
+    /// Mean time between two pipelines
+    pub pipeline_interval: f64,
+    pub pipeline_duration: LogNormalParameters,
+    pub status_weights: StatusWeights,
+    pub ref_weights: RefWeights,
+    /// Time after which a new release branch is used
+    pub release_interval: i64,
+    /// Probability that a pipeline on a feature branch is run on a new branch instead of an existing one
+    pub new_branch_probability: f64,
+    /// Mean time a feature branch exists before it is merged or closed
+    pub branch_lifetime: f64,
+    /// Probability that a feature branch is merged instead of being closed
+    pub merge_probability: f64,
+    /// Mean number of accesses to a successful pipeline
+    pub accesses_per_pipeline: f64,
+    /// Factor applied to the access count of failed pipelines
+    pub failed_access_factor: f64,
+    pub access_decay: AccessDecay,
+    /// Fraction of accesses made by automated tools
+    pub automatic_access_fraction: f64,
+    pub environments: Vec<String>,
+    pub test_suites: Vec<String>,
+    /// Number of distinct environment and test suite combinations run by each pipeline
+    pub jobs_per_pipeline: usize,
+    /// Size distribution of a job, each environment and test suite combination is scaled by a random factor
+    pub job_size: LogNormalParameters,
+    /// Standard deviation of the logarithm of the scale factor of each job
+    pub job_size_spread: f64,
+    /// Number of JobSizeSamples recorded for each environment and test suite combination
+    pub samples_per_job: usize,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            start: 1_600_000_000,
+            duration: 60 * 60 * 24 * 14,
+            pipeline_interval: 600.0,
+            pipeline_duration: LogNormalParameters {
+                median: 1800.0,
+                sigma: 0.5,
+            },
+            status_weights: StatusWeights {
+                success: 0.7,
+                failed: 0.2,
+                canceled: 0.1,
+            },
+            ref_weights: RefWeights {
+                master: 0.15,
+                premaster: 0.1,
+                release: 0.05,
+                feature: 0.7,
+            },
+            release_interval: 60 * 60 * 24 * 30,
+            new_branch_probability: 0.2,
+            branch_lifetime: 60.0 * 60.0 * 24.0 * 3.0,
+            merge_probability: 0.8,
+            accesses_per_pipeline: 1.5,
+            failed_access_factor: 2.0,
+            access_decay: AccessDecay::Exponential {
+                mean: 60.0 * 60.0 * 12.0,
+            },
+            automatic_access_fraction: 0.1,
+            environments: vec![
+                "chrome".to_owned(),
+                "firefox".to_owned(),
+                "safari".to_owned(),
+            ],
+            test_suites: vec![
+                "smoke".to_owned(),
+                "regression".to_owned(),
+                "ui".to_owned(),
+                "api".to_owned(),
+            ],
+            jobs_per_pipeline: 4,
+            job_size: LogNormalParameters {
+                median: 150_000_000.0,
+                sigma: 1.0,
+            },
+            job_size_spread: 0.5,
+            samples_per_job: 50,
+        }
+    }
+}
+
+impl WorkloadConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+struct FeatureBranch {
+    name: String,
+    merge_request: i64,
+    closed_at: i64,
+}
+
+/// Generates a workload database by simulating the creation of pipelines and accesses to them
+pub struct WorkloadGenerator {
+    config: WorkloadConfig,
+    rng: StdRng,
+
+    /// Events as (timestamp, kind, key), sorted before being written
+    events: Vec<(i64, SimulationEventKind, i64)>,
+    branches: Vec<FeatureBranch>,
+    next_access_id: i64,
+    next_merge_request_event_id: i64,
+    next_job_id: i64,
+}
+
+impl WorkloadGenerator {
+    pub fn new(config: WorkloadConfig, seed: u64) -> Self {
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+            events: Vec::new(),
+            branches: Vec::new(),
+            next_access_id: 1,
+            next_merge_request_event_id: 1,
+            next_job_id: 1,
+        }
+    }
+
+    pub async fn generate(mut self, path: &Path) -> Result<()> {
+        if path.exists() {
+            bail!("Refusing to overwrite existing database {}", path.display());
+        }
+
+        if self.config.environments.len() * self.config.test_suites.len()
+            < self.config.jobs_per_pipeline
+        {
+            bail!("Not enough environment and test suite combinations for the number of jobs per pipeline");
+        }
+
+        let mut con = create_database(path).await?;
+        let mut tx = con.begin().await?;
+
+        let jobs = self.insert_job_size_samples(&mut tx).await?;
+        self.insert_pipelines(&jobs, &mut tx).await?;
+        self.close_branches(i64::MAX, &mut tx).await?;
+
+        self.events.sort_unstable();
+        for (timestamp, kind, key) in self.events.iter() {
+            sqlx::query("INSERT INTO SimulationEvent (timestamp, kind, key) VALUES ($1, $2, $3)")
+                .bind(timestamp)
+                .bind(*kind)
+                .bind(key)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        con.close().await?;
+
+        Ok(())
+    }
+
+    /// Records size samples for all combinations of environments and test suites and returns the job names
+    async fn insert_job_size_samples(
+        &mut self,
+        con: &mut SqliteConnection,
+    ) -> Result<Vec<(String, String)>> {
+        let spread = LogNormal::new(0.0, self.config.job_size_spread)?;
+        let mut jobs = Vec::new();
+
+        for environment in self.config.environments.iter() {
+            for test_suite in self.config.test_suites.iter() {
+                let mut parameters = self.config.job_size;
+                parameters.median *= spread.sample(&mut self.rng);
+                let distribution = parameters.distribution()?;
+
+                for _ in 0..self.config.samples_per_job {
+                    sqlx::query(
+                        "INSERT INTO JobSizeSample (environment, testSuite, bytes) VALUES ($1, $2, $3)",
+                    )
+                    .bind(environment)
+                    .bind(test_suite)
+                    .bind(distribution.sample(&mut self.rng) as i64)
+                    .execute(&mut *con)
+                    .await?;
+                }
+
+                jobs.push((environment.clone(), test_suite.clone()));
+            }
+        }
+
+        Ok(jobs)
+    }
+
+    async fn insert_pipelines(
+        &mut self,
+        jobs: &[(String, String)],
+        con: &mut SqliteConnection,
+    ) -> Result<()> {
+        let arrival = Exp::new(1.0 / self.config.pipeline_interval)?;
+        let duration = self.config.pipeline_duration.distribution()?;
+        let statuses = ["success", "failed", "canceled"];
+        let status_weights = self.config.status_weights;
+        let status_distribution = WeightedIndex::new([
+            status_weights.success,
+            status_weights.failed,
+            status_weights.canceled,
+        ])?;
+
+        let mut timestamp = self.config.start as f64;
+        let end = (self.config.start + self.config.duration) as f64;
+        let mut id = 1;
+
+        while timestamp < end {
+            let created_at = timestamp as i64;
+            self.close_branches(created_at, con).await?;
+
+            let pipeline_ref = self.pipeline_ref(created_at, con).await?;
+            let pipeline_duration = duration.sample(&mut self.rng) as i64;
+            let finished_at = created_at + pipeline_duration;
+            let status = statuses[status_distribution.sample(&mut self.rng)];
+            let pipeline_jobs = jobs
+                .choose_multiple(&mut self.rng, self.config.jobs_per_pipeline)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            sqlx::query("INSERT INTO Pipeline (id, status, duration, createdAt, finishedAt, ref, jobs) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+                .bind(id)
+                .bind(status)
+                .bind(pipeline_duration)
+                .bind(created_at)
+                .bind(finished_at)
+                .bind(&pipeline_ref)
+                .bind(
+                    pipeline_jobs
+                        .iter()
+                        .map(|(environment, test_suite)| format!("{}:{}", environment, test_suite))
+                        .collect::<Vec<_>>()
+                        .join(";"),
+                )
+                .execute(&mut *con)
+                .await?;
+
+            self.events
+                .push((created_at, SimulationEventKind::PipelineCreated, id));
+            self.events
+                .push((finished_at, SimulationEventKind::PipelineFinished, id));
+
+            let mut access_count = self.config.accesses_per_pipeline;
+            if status == "failed" {
+                access_count *= self.config.failed_access_factor;
+            }
+
+            self.insert_accesses(id, finished_at, access_count, &pipeline_jobs, con)
+                .await?;
+
+            timestamp += arrival.sample(&mut self.rng);
+            id += 1;
+        }
+
+        Ok(())
+    }
+
+    async fn insert_accesses(
+        &mut self,
+        pipeline: i64,
+        finished_at: i64,
+        mean_count: f64,
+        jobs: &[(String, String)],
+        con: &mut SqliteConnection,
+    ) -> Result<()> {
+        if mean_count <= 0.0 {
+            return Ok(());
+        }
+
+        let count: f64 = Poisson::new(mean_count)?.sample(&mut self.rng);
+
+        for _ in 0..(count as usize) {
+            let timestamp = finished_at + self.config.access_decay.sample(&mut self.rng)? as i64;
+            let is_automatic = self.rng.gen_bool(self.config.automatic_access_fraction);
+            let (environment, test_suite) = jobs.choose(&mut self.rng).unwrap();
+            let job = format!("logs_{}_{}-{}", environment, test_suite, self.next_job_id);
+            self.next_job_id += 1;
+
+            sqlx::query("INSERT INTO AccessLog (id, timestamp, method, path, status, bytes, referee, userAgent, isAutomatic, isIrrelevant, repository, pipeline, job, file) VALUES ($1, $2, 'GET', $3, 200, 0, '-', $4, $5, 0, 'synthetic', $6, $7, 'index.html')")
+                .bind(self.next_access_id)
+                .bind(timestamp)
+                .bind(format!("/synthetic/{}/{}/index.html", pipeline, job))
+                .bind(if is_automatic { "curl" } else { "Mozilla/5.0" })
+                .bind(is_automatic)
+                .bind(pipeline)
+                .bind(&job)
+                .execute(&mut *con)
+                .await?;
+
+            self.events
+                .push((timestamp, SimulationEventKind::Access, self.next_access_id));
+            self.next_access_id += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Picks the ref of a new pipeline, potentially opening a new feature branch
+    async fn pipeline_ref(&mut self, timestamp: i64, con: &mut SqliteConnection) -> Result<String> {
+        let weights = self.config.ref_weights;
+        let ref_distribution = WeightedIndex::new([
+            weights.master,
+            weights.premaster,
+            weights.release,
+            weights.feature,
+        ])?;
+
+        Ok(match ref_distribution.sample(&mut self.rng) {
+            0 => "master".to_owned(),
+            1 => "premaster".to_owned(),
+            2 => format!(
+                "release/1.{}",
+                (timestamp - self.config.start) / self.config.release_interval
+            ),
+            _ => {
+                if self.branches.is_empty() || self.rng.gen_bool(self.config.new_branch_probability)
+                {
+                    self.open_branch(timestamp, con).await?
+                } else {
+                    self.branches.choose(&mut self.rng).unwrap().name.clone()
+                }
+            }
+        })
+    }
+
+    async fn open_branch(&mut self, timestamp: i64, con: &mut SqliteConnection) -> Result<String> {
+        let lifetime = Exp::new(1.0 / self.config.branch_lifetime)?.sample(&mut self.rng);
+        let merge_request = self.next_merge_request_event_id;
+        let branch = FeatureBranch {
+            name: format!("gitlabCI/TPH-{}", merge_request),
+            merge_request,
+            closed_at: timestamp + lifetime as i64,
+        };
+
+        self.insert_merge_request_event(&branch, "opened", "open", timestamp, con)
+            .await?;
+
+        let name = branch.name.clone();
+        self.branches.push(branch);
+
+        Ok(name)
+    }
+
+    /// Merges or closes all feature branches whose lifetime ended before the given timestamp
+    async fn close_branches(&mut self, timestamp: i64, con: &mut SqliteConnection) -> Result<()> {
+        let (closed, open) = std::mem::take(&mut self.branches)
+            .into_iter()
+            .partition::<Vec<_>, _>(|b| b.closed_at < timestamp);
+
+        self.branches = open;
+
+        for branch in closed {
+            if self.rng.gen_bool(self.config.merge_probability) {
+                self.insert_merge_request_event(&branch, "merged", "merge", branch.closed_at, con)
+                    .await?;
+            } else {
+                self.insert_merge_request_event(&branch, "closed", "close", branch.closed_at, con)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn insert_merge_request_event(
+        &mut self,
+        branch: &FeatureBranch,
+        status: &str,
+        action: &str,
+        timestamp: i64,
+        con: &mut SqliteConnection,
+    ) -> Result<()> {
+        let id = self.next_merge_request_event_id;
+        self.next_merge_request_event_id += 1;
+
+        sqlx::query("INSERT INTO MergeRequestEvent (eventID, mergeRequestID, status, sourceBranch, targetBranch, action, timestamp) VALUES ($1, $2, $3, $4, 'master', $5, $6)")
+            .bind(id)
+            .bind(branch.merge_request)
+            .bind(status)
+            .bind(&branch.name)
+            .bind(action)
+            .bind(timestamp)
+            .execute(&mut *con)
+            .await?;
+
+        self.events
+            .push((timestamp, SimulationEventKind::MergeRequestEvent, id));
+
+        Ok(())
+    }
+}